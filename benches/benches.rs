@@ -7,7 +7,7 @@ mod collapse {
     use wfc_rust::graph::graph::Graph;
     use wfc_rust::io::text_parser::parse;
     use wfc_rust::io::utils::make_edges_8_way_grid;
-    use wfc_rust::wfc::collapse::collapse;
+    use wfc_rust::wfc::collapse::collapse_owned;
 
     pub fn bench_collapse(bench: &mut Bencher) {
         let out_width = 100;
@@ -19,7 +19,7 @@ mod collapse {
             let output_edges = make_edges_8_way_grid(out_width, out_depth);
             let output_graph = Graph::new(output_vertices, output_edges, all_labels);
 
-            bench.iter(|| collapse(&input_graph.rules(), output_graph.clone(), Some(1), None))
+            bench.iter(|| collapse_owned(&input_graph.rules(), output_graph.clone(), Some(1), None))
         }
     }
 }
@@ -1,4 +1,6 @@
 use hashbrown::HashMap;
+use indexmap::IndexMap;
+use std::hash::Hash;
 use std::ops::{Index, AddAssign};
 use std::fmt::{Debug, Formatter, Result};
 use crate::MSu16xNU;
@@ -6,6 +8,7 @@ use crate::MSu16xNU;
 pub type VertexIndex = u32; // each unique vertex in a graph
 pub type EdgeDirection = u16; // the directional relationship between two vertices
 pub type Edges = HashMap<VertexIndex, Vec<(VertexIndex, EdgeDirection)>>;
+pub type Neighbours = [(VertexIndex, EdgeDirection)];
 pub type Vertices = Vec<MSu16xNU>;
 
 //                        vertex label (index of LabelFrequencies vector)
@@ -74,6 +77,129 @@ impl Graph {
                 rules
             })
     }
+
+    /// Compile the edges of this graph into a compressed sparse row adjacency.
+    pub fn compact_edges(&self) -> CompactEdges {
+        CompactEdges::new(&self.edges, self.vertices.len())
+    }
+}
+
+/// Allow label `to`, with `frequency`, in `direction` of label `from`.
+/// Declaring the same rule again has no further effect.
+pub fn insert_rule(rules: &mut Rules, direction: EdgeDirection, from: usize, to: usize, frequency: u16) {
+    let mut set = MSu16xNU::empty();
+    set.insert(to, frequency);
+    let labels = rules.entry((direction, from)).or_insert_with(MSu16xNU::empty);
+    *labels = labels.union(&set);
+}
+
+/// Add the keys of a sample to a shared key table, summing the frequencies of keys already present.
+/// Frequencies saturate at `u16::MAX`.
+/// Returns the mapping from each label of `keys` to its label in `shared`: `mapping[label] = shared label`.
+pub fn merge_keys<K: Hash + Eq + Clone>(shared: &mut IndexMap<K, u16>, keys: &IndexMap<K, u16>) -> Vec<usize> {
+    keys
+        .iter()
+        .map(|(key, frequency)| {
+            let entry = shared.entry(key.clone());
+            let label = entry.index();
+            entry.and_modify(|f| *f = f.saturating_add(*frequency)).or_insert(*frequency);
+            label
+        })
+        .collect()
+}
+
+/// Move every label in a set to the label given by `mapping`.
+pub fn remap_labels(labels: &MSu16xNU, mapping: &[usize]) -> MSu16xNU {
+    labels
+        .into_iter()
+        .enumerate()
+        .filter(|(_, frequency)| frequency > &0)
+        .fold(MSu16xNU::empty(), |mut acc, (label, frequency)| {
+            let mut set = MSu16xNU::empty();
+            set.insert(mapping[label], frequency);
+            acc.add_assign(set);
+            acc
+        })
+}
+
+/// Move the labels of every rule to the labels given by `mapping`.
+pub fn remap_rules(rules: &Rules, mapping: &[usize]) -> Rules {
+    rules
+        .iter()
+        .map(|((direction, label), labels)| ((*direction, mapping[*label]), remap_labels(labels, mapping)))
+        .collect()
+}
+
+/// Sum the frequencies of two label sets, saturating at `u16::MAX` instead of overflowing.
+pub fn saturating_sum(a: &MSu16xNU, b: &MSu16xNU) -> MSu16xNU {
+    a.into_iter()
+        .zip(b)
+        .enumerate()
+        .fold(MSu16xNU::empty(), |mut acc, (label, (x, y))| {
+            acc.insert(label, x.saturating_add(y));
+            acc
+        })
+}
+
+/// Combine the rules of several samples that share the same label indices, summing label frequencies.
+pub fn merge_rules<I: IntoIterator<Item=Rules>>(rules: I) -> Rules {
+    rules
+        .into_iter()
+        .fold(HashMap::new(), |mut merged, sample_rules| {
+            sample_rules.into_iter().for_each(|(key, labels)| {
+                merged
+                    .entry(key)
+                    .and_modify(|l| *l = saturating_sum(l, &labels))
+                    .or_insert(labels);
+            });
+            merged
+        })
+}
+
+/// Compressed sparse row (CSR) form of `Edges`.
+/// The neighbours of vertex `i` are stored contiguously in
+/// `neighbours[offsets[i]..offsets[i + 1]]`, avoiding a heap allocation and a hash lookup per vertex.
+/// `Edges` remain the storage of a `Graph`, collapses compile this form once before propagating.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactEdges {
+    offsets: Vec<usize>,
+    neighbours: Vec<(VertexIndex, EdgeDirection)>,
+}
+
+impl CompactEdges {
+    pub fn new(edges: &Edges, vertices_len: usize) -> CompactEdges {
+        let mut offsets = Vec::with_capacity(vertices_len + 1);
+        let mut neighbours = Vec::with_capacity(edges.values().map(Vec::len).sum());
+        offsets.push(0);
+        (0..vertices_len as VertexIndex).for_each(|index| {
+            if let Some(connections) = edges.get(&index) {
+                neighbours.extend_from_slice(connections)
+            }
+            offsets.push(neighbours.len());
+        });
+        CompactEdges { offsets, neighbours }
+    }
+
+    /// The number of vertices in the adjacency.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn neighbours(&self, index: VertexIndex) -> &Neighbours {
+        let index = index as usize;
+        &self.neighbours[self.offsets[index]..self.offsets[index + 1]]
+    }
+
+    /// Expand back into the hash map representation.
+    pub fn to_edges(&self) -> Edges {
+        (0..self.len() as VertexIndex)
+            .map(|index| (index, self.neighbours(index).to_vec()))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -233,4 +359,104 @@ mod graph_tests {
 
         assert_eq!(test_graph.rules(), result);
     }
+
+    #[test]
+    fn test_insert_rule() {
+        let mut rules: Rules = HashMap::new();
+        insert_rule(&mut rules, 1, 0, 2, 3);
+        insert_rule(&mut rules, 1, 0, 1, 1);
+        insert_rule(&mut rules, 1, 0, 2, 3);
+        assert_eq!(rules, hash_map(&[((1, 0), [0, 1, 3].iter().collect())]));
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let mut shared: IndexMap<char, u16> = IndexMap::new();
+        let mut keys_a: IndexMap<char, u16> = IndexMap::new();
+        keys_a.insert('a', 2);
+        keys_a.insert('b', 1);
+        let mut keys_b: IndexMap<char, u16> = IndexMap::new();
+        keys_b.insert('c', 4);
+        keys_b.insert('a', 3);
+
+        assert_eq!(merge_keys(&mut shared, &keys_a), vec![0, 1]);
+        assert_eq!(merge_keys(&mut shared, &keys_b), vec![2, 0]);
+        assert_eq!(shared.into_iter().collect::<Vec<_>>(), vec![('a', 5), ('b', 1), ('c', 4)]);
+    }
+
+    #[test]
+    fn test_saturating_sum() {
+        let a: MSu16xNU = [1, u16::MAX - 1, 0].iter().collect();
+        let b: MSu16xNU = [2, 3, 0].iter().collect();
+        let expected: MSu16xNU = [3, u16::MAX, 0].iter().collect();
+        assert_eq!(saturating_sum(&a, &b), expected);
+
+        let mut shared: IndexMap<char, u16> = IndexMap::new();
+        shared.insert('a', u16::MAX);
+        let mut keys: IndexMap<char, u16> = IndexMap::new();
+        keys.insert('a', 1);
+        merge_keys(&mut shared, &keys);
+        assert_eq!(shared.get(&'a'), Some(&u16::MAX));
+    }
+
+    #[test]
+    fn test_remap_labels() {
+        let labels: MSu16xNU = [3, 0, 1].iter().collect();
+        let expected: MSu16xNU = [0, 1, 0, 3].iter().collect();
+        assert_eq!(remap_labels(&labels, &[3, 0, 1]), expected);
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        /*
+        sample a: x --- y    (x = 0, y = 1)
+        sample b: z --- x    (z = 0, x = 1)
+
+        East = 0, West = 1
+        */
+        let edges: Edges = hash_map(&[(0, vec![(1, 0)]), (1, vec![(0, 1)])]);
+        let sample_a = Graph::new(
+            vec![[1, 0].iter().collect(), [0, 1].iter().collect()],
+            edges.clone(),
+            [1, 1].iter().collect(),
+        );
+        let sample_b = Graph::new(
+            vec![[1, 0].iter().collect(), [0, 1].iter().collect()],
+            edges,
+            [1, 1].iter().collect(),
+        );
+
+        // shared keys: x = 0, y = 1, z = 2
+        let rules_a = remap_rules(&sample_a.rules(), &[0, 1]);
+        let rules_b = remap_rules(&sample_b.rules(), &[2, 0]);
+        let result = merge_rules(vec![rules_a, rules_b]);
+
+        let expected: Rules = hash_map(&[
+            ((0, 0), [0, 1, 0].iter().collect()),
+            ((0, 2), [1, 0, 0].iter().collect()),
+            ((1, 1), [1, 0, 0].iter().collect()),
+            ((1, 0), [0, 0, 1].iter().collect()),
+        ]);
+        assert_eq!(result, expected);
+
+        // frequencies of identical rules are summed
+        let doubled = merge_rules(vec![result.clone(), result]);
+        assert_eq!(doubled.get(&(0, 0)).unwrap(), &[0, 2, 0].iter().collect());
+    }
+
+    #[test]
+    fn test_compact_edges() {
+        let compact = CompactEdges::new(&graph_edges(), 5);
+
+        assert_eq!(compact.len(), 5);
+        assert_eq!(compact.neighbours(0), &[(1, 0), (3, 2)]);
+        assert_eq!(compact.neighbours(2), &[(3, 1), (1, 3)]);
+        assert_eq!(compact.neighbours(3), &[(0, 3), (2, 0)]);
+        // vertices with no edges have no neighbours
+        assert!(compact.neighbours(4).is_empty());
+
+        let mut edges = graph_edges();
+        edges.insert(4, Vec::new());
+        assert_eq!(compact.to_edges(), edges);
+    }
 }
@@ -0,0 +1,161 @@
+use crate::graph::graph::Graph;
+use crate::io::graph_parser::VertexNames;
+use crate::io::text_parser::CONTRADICT_TOKEN;
+use hashbrown::HashMap;
+use image::Rgb;
+use std::fs::write;
+use std::io::Error;
+
+// label index -> fill colour of collapsed vertices
+pub type Palette = HashMap<usize, Rgb<u8>>;
+
+/// Export a graph to Graphviz DOT.
+/// Each vertex is written with its collapsed label, or its candidate set when it is not collapsed, and
+/// the label indices in a `labels` attribute, so the file can be read back with `graph_parser::parse_dot`.
+/// `label_names` gives display names of labels by index and `names` the vertex ids to write;
+/// vertex indexes are used where either is missing.
+pub fn render(
+    filename: &str,
+    graph: &Graph,
+    label_names: &[String],
+    names: Option<&VertexNames>,
+    palette: Option<&Palette>,
+) -> Result<(), Error> {
+    write(filename, to_dot(graph, label_names, names, palette))
+}
+
+pub fn to_dot(
+    graph: &Graph,
+    label_names: &[String],
+    names: Option<&VertexNames>,
+    palette: Option<&Palette>,
+) -> String {
+    let vertex_name = |index: usize| {
+        names
+            .and_then(|names| names.get_index(index))
+            .map_or_else(|| index.to_string(), |(name, _)| name.clone())
+    };
+    let label_name = |label: usize| {
+        label_names.get(label).cloned().unwrap_or_else(|| label.to_string())
+    };
+
+    let mut dot = String::from("digraph {\n");
+
+    graph.vertices.iter().enumerate().for_each(|(index, labels)| {
+        let candidates: Vec<usize> = labels
+            .into_iter()
+            .enumerate()
+            .filter(|(_, frequency)| frequency > &0)
+            .map(|(label, _)| label)
+            .collect();
+
+        let display = match candidates.as_slice() {
+            [] => CONTRADICT_TOKEN.to_string(),
+            [label] => label_name(*label),
+            _ => format!("{{{}}}", candidates.iter().map(|label| label_name(*label)).collect::<Vec<_>>().join(",")),
+        };
+        let indexes = candidates.iter().map(|label| label.to_string()).collect::<Vec<_>>().join(",");
+
+        let mut attributes = vec![
+            format!("label={}", quote(&display)),
+            format!("labels={}", quote(&indexes)),
+        ];
+        match candidates.as_slice() {
+            [] => attributes.push("color=red".to_string()),
+            [label] => {
+                if let Some(Rgb([r, g, b])) = palette.and_then(|palette| palette.get(label)) {
+                    attributes.push("style=filled".to_string());
+                    attributes.push(format!("fillcolor=\"#{:02x}{:02x}{:02x}\"", r, g, b));
+                }
+            }
+            _ => attributes.push("style=dashed".to_string()),
+        }
+
+        dot.push_str(&format!("    {} [{}];\n", quote(&vertex_name(index)), attributes.join(", ")));
+    });
+
+    let mut edges: Vec<_> = graph.edges.iter().collect();
+    edges.sort_by_key(|(from, _)| **from);
+    edges.into_iter().for_each(|(from, neighbours)| {
+        neighbours.iter().for_each(|(to, direction)| {
+            dot.push_str(&format!(
+                "    {} -> {} [label={}];\n",
+                quote(&vertex_name(*from as usize)),
+                quote(&vertex_name(*to as usize)),
+                direction
+            ));
+        })
+    });
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::graph_parser::parse_dot;
+    use crate::utils::hash_map;
+    use crate::MSu16xNU;
+
+    fn test_graph() -> Graph {
+        Graph::new(
+            vec![
+                [1, 0, 0].iter().collect(),
+                [0, 2, 1].iter().collect(),
+                [0, 0, 0].iter().collect(),
+            ],
+            hash_map(&[
+                (0, vec![(1, 0)]),
+                (1, vec![(0, 1), (2, 0)]),
+                (2, vec![(1, 1)]),
+            ]),
+            [1, 2, 1].iter().collect(),
+        )
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let label_names = vec!["a".to_string(), "b".to_string(), "\"c\"".to_string()];
+        let mut palette = Palette::new();
+        palette.insert(0, Rgb([255, 0, 16]));
+
+        let dot = to_dot(&test_graph(), &label_names, None, Some(&palette));
+        let expected = "digraph {
+    \"0\" [label=\"a\", labels=\"0\", style=filled, fillcolor=\"#ff0010\"];
+    \"1\" [label=\"{b,\\\"c\\\"}\", labels=\"1,2\", style=dashed];
+    \"2\" [label=\"❌\", labels=\"\", color=red];
+    \"0\" -> \"1\" [label=0];
+    \"1\" -> \"0\" [label=1];
+    \"1\" -> \"2\" [label=0];
+    \"2\" -> \"1\" [label=1];
+}
+";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let graph = test_graph();
+        let mut names = VertexNames::new();
+        names.insert("x".to_string(), 0);
+        names.insert("y".to_string(), 1);
+        names.insert("z".to_string(), 2);
+
+        let filename = "resources/test/dot_render_test.dot";
+        render(filename, &graph, &[], Some(&names), None).unwrap();
+        let (parsed, parsed_names) = parse_dot(filename, &graph.all_labels).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(parsed_names, names);
+        assert_eq!(parsed.edges, graph.edges);
+        assert_eq!(parsed.vertices[0], graph.vertices[0]);
+        assert_eq!(parsed.vertices[1], graph.vertices[1]);
+        // contradictions are read back as an empty restriction
+        assert_eq!(parsed.vertices[2], MSu16xNU::empty());
+    }
+}
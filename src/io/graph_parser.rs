@@ -0,0 +1,453 @@
+use crate::graph::graph::{EdgeDirection, Edges, Graph, VertexIndex};
+use crate::io::utils::invalid_data;
+use crate::MSu16xNU;
+use hashbrown::HashMap;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::io::Error;
+use std::path::Path;
+
+// vertex name in the file -> vertex index in the graph
+pub type VertexNames = IndexMap<String, VertexIndex>;
+
+/// Load an output graph topology from a Graphviz DOT (`.dot`, `.gv`) or JSON edge-list (`.json`) file.
+/// Every vertex starts with `all_labels`, unless the file restricts it to a set of label indices.
+pub fn parse(filename: &str, all_labels: &MSu16xNU) -> Result<(Graph, VertexNames), Error> {
+    match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+        Some("dot") | Some("gv") => parse_dot(filename, all_labels),
+        Some("json") => parse_json(filename, all_labels),
+        _ => Err(invalid_data(format!("Unrecognised graph file extension: {}", filename))),
+    }
+}
+
+/*
+A subset of the DOT language is supported. Graphs must be directed, every edge needs a numeric
+`label` (or `direction`) attribute holding its direction, and vertices may be restricted to
+a list of label indices with a `labels` attribute:
+
+digraph {
+    a [labels="0, 2"];
+    a -> b -> c [label=1];
+}
+*/
+pub fn parse_dot(filename: &str, all_labels: &MSu16xNU) -> Result<(Graph, VertexNames), Error> {
+    read_to_string(filename).and_then(|string| dot_builder(&string)?.build(all_labels))
+}
+
+/*
+{
+    "vertices": ["a", "b"],
+    "edges": [{ "from": "a", "to": "b", "direction": 1 }],
+    "labels": { "a": [0, 2] }
+}
+
+`vertices` is optional, and only needed to declare vertices without any edges.
+*/
+pub fn parse_json(filename: &str, all_labels: &MSu16xNU) -> Result<(Graph, VertexNames), Error> {
+    read_to_string(filename).and_then(|string| json_builder(&string)?.build(all_labels))
+}
+
+#[derive(Default)]
+struct GraphBuilder {
+    names: VertexNames,
+    edges: Vec<(VertexIndex, VertexIndex, EdgeDirection)>,
+    restrictions: HashMap<VertexIndex, Vec<usize>>,
+}
+
+impl GraphBuilder {
+    fn vertex(&mut self, name: &str) -> VertexIndex {
+        let next_index = self.names.len() as VertexIndex;
+        *self.names.entry(name.to_string()).or_insert(next_index)
+    }
+
+    fn edge(&mut self, from: &str, to: &str, direction: EdgeDirection) {
+        let from = self.vertex(from);
+        let to = self.vertex(to);
+        self.edges.push((from, to, direction));
+    }
+
+    fn restrict(&mut self, name: &str, labels: Vec<usize>) {
+        let index = self.vertex(name);
+        self.restrictions.insert(index, labels);
+    }
+
+    fn build(self, all_labels: &MSu16xNU) -> Result<(Graph, VertexNames), Error> {
+        let mut vertices = vec![*all_labels; self.names.len()];
+        for (index, labels) in self.restrictions {
+            vertices[index as usize] = restrict(all_labels, &labels)?;
+        }
+
+        let mut edges: Edges = (0..self.names.len() as VertexIndex)
+            .map(|index| (index, Vec::new()))
+            .collect();
+        self.edges
+            .into_iter()
+            .for_each(|(from, to, direction)| edges.get_mut(&from).unwrap().push((to, direction)));
+
+        Ok((Graph::new(vertices, edges, *all_labels), self.names))
+    }
+}
+
+// Keep only the given labels of all_labels, along with their frequencies.
+fn restrict(all_labels: &MSu16xNU, labels: &[usize]) -> Result<MSu16xNU, Error> {
+    labels.iter().try_fold(MSu16xNU::empty(), |mut set, label| {
+        match all_labels.into_iter().nth(*label) {
+            Some(frequency) if frequency > 0 => {
+                set.insert(*label, frequency);
+                Ok(set)
+            }
+            _ => Err(invalid_data(format!("Label {} is not in the set of all labels", label))),
+        }
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonName {
+    Number(u64),
+    Text(String),
+}
+
+impl JsonName {
+    fn name(&self) -> String {
+        match self {
+            JsonName::Number(number) => number.to_string(),
+            JsonName::Text(text) => text.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonEdge {
+    from: JsonName,
+    to: JsonName,
+    direction: EdgeDirection,
+}
+
+#[derive(Deserialize)]
+struct JsonGraph {
+    #[serde(default)]
+    vertices: Vec<JsonName>,
+    edges: Vec<JsonEdge>,
+    #[serde(default)]
+    labels: BTreeMap<String, Vec<usize>>,
+}
+
+fn json_builder(string: &str) -> Result<GraphBuilder, Error> {
+    let json: JsonGraph = serde_json::from_str(string).map_err(|e| invalid_data(e.to_string()))?;
+    let mut builder = GraphBuilder::default();
+    json.vertices.iter().for_each(|name| {
+        builder.vertex(&name.name());
+    });
+    json.edges
+        .iter()
+        .for_each(|edge| builder.edge(&edge.from.name(), &edge.to.name(), edge.direction));
+    json.labels
+        .into_iter()
+        .for_each(|(name, labels)| builder.restrict(&name, labels));
+    Ok(builder)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Id(String),
+    Punct(char),
+    Arrow,
+    UndirectedArrow,
+}
+
+fn is_id_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_' || char == '.' || char == '-'
+}
+
+fn tokenise(string: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = string.chars().peekable();
+    let mut line_start = true;
+    while let Some(char) = chars.next() {
+        match char {
+            '\n' => {
+                line_start = true;
+                continue
+            }
+            c if c.is_whitespace() => continue,
+            // preprocessor style lines
+            '#' if line_start => while chars.next_if(|c| c != &'\n').is_some() {},
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| c != &'\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' { break }
+                    previous = c;
+                }
+            }
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push(Token::Arrow)
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                tokens.push(Token::UndirectedArrow)
+            }
+            '"' => {
+                let mut id = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => id.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => id.push(c),
+                        None => return Err(invalid_data("Unterminated string in DOT file".to_string())),
+                    }
+                }
+                tokens.push(Token::Id(id))
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => tokens.push(Token::Punct(char)),
+            c if is_id_char(c) => {
+                let mut id = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_id_char(*c) && c != &'-') {
+                    id.push(c)
+                }
+                tokens.push(Token::Id(id))
+            }
+            c => return Err(invalid_data(format!("Unexpected character in DOT file: {}", c))),
+        }
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+struct DotParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl DotParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Error> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(invalid_data(format!("Expected {:?} in DOT file, found {:?}", expected, other))),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Some(Token::Id(id)) => Ok(id),
+            other => Err(invalid_data(format!("Expected an identifier in DOT file, found {:?}", other))),
+        }
+    }
+
+    // zero or more bracketed attribute lists: [a=b, c=d][e=f]
+    fn attributes(&mut self) -> Result<HashMap<String, String>, Error> {
+        let mut attributes = HashMap::new();
+        while self.peek() == Some(&Token::Punct('[')) {
+            self.next();
+            loop {
+                match self.peek() {
+                    Some(Token::Punct(']')) => {
+                        self.next();
+                        break
+                    }
+                    Some(Token::Punct(',')) | Some(Token::Punct(';')) => {
+                        self.next();
+                    }
+                    _ => {
+                        let key = self.id()?;
+                        self.expect(Token::Punct('='))?;
+                        let value = self.id()?;
+                        attributes.insert(key, value);
+                    }
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn statement(&mut self, builder: &mut GraphBuilder) -> Result<(), Error> {
+        let id = self.id()?;
+        match (id.as_str(), self.peek()) {
+            // default attribute statements don't affect the topology
+            ("graph", Some(Token::Punct('['))) | ("node", Some(Token::Punct('['))) | ("edge", Some(Token::Punct('['))) => {
+                self.attributes()?;
+            }
+            (_, Some(Token::Punct('='))) => {
+                self.next();
+                self.id()?;
+            }
+            (_, Some(Token::Arrow)) => {
+                let mut chain = vec![id];
+                while self.peek() == Some(&Token::Arrow) {
+                    self.next();
+                    chain.push(self.id()?);
+                }
+                let attributes = self.attributes()?;
+                let direction = attributes
+                    .get("direction")
+                    .or_else(|| attributes.get("label"))
+                    .ok_or_else(|| invalid_data(format!("Edge from {} has no direction label", chain[0])))?
+                    .parse::<EdgeDirection>()
+                    .map_err(|e| invalid_data(format!("Invalid edge direction: {}", e)))?;
+                chain.windows(2).for_each(|pair| builder.edge(&pair[0], &pair[1], direction));
+            }
+            (_, Some(Token::UndirectedArrow)) => {
+                return Err(invalid_data("Undirected edges are not supported, use digraph and ->".to_string()))
+            }
+            _ => {
+                builder.vertex(&id);
+                let attributes = self.attributes()?;
+                if let Some(labels) = attributes.get("labels") {
+                    builder.restrict(&id, parse_labels(labels)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_labels(labels: &str) -> Result<Vec<usize>, Error> {
+    labels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|label| !label.is_empty())
+        .map(|label| label.parse().map_err(|_| invalid_data(format!("Invalid label index: {}", label))))
+        .collect()
+}
+
+fn dot_builder(string: &str) -> Result<GraphBuilder, Error> {
+    let mut parser = DotParser { tokens: tokenise(string)?, position: 0 };
+    let mut builder = GraphBuilder::default();
+
+    if parser.peek() == Some(&Token::Id("strict".to_string())) {
+        parser.next();
+    }
+    match parser.id()?.as_str() {
+        "digraph" => (),
+        "graph" => return Err(invalid_data("Undirected graphs are not supported, use digraph".to_string())),
+        other => return Err(invalid_data(format!("Expected digraph in DOT file, found {}", other))),
+    }
+    if let Some(Token::Id(_)) = parser.peek() {
+        parser.next();
+    }
+    parser.expect(Token::Punct('{'))?;
+    loop {
+        match parser.peek() {
+            Some(Token::Punct('}')) => break,
+            Some(Token::Punct(';')) | Some(Token::Punct(',')) => {
+                parser.next();
+            }
+            Some(Token::Punct('{')) => return Err(invalid_data("Subgraphs are not supported".to_string())),
+            None => return Err(invalid_data("Unexpected end of DOT file".to_string())),
+            _ => parser.statement(&mut builder)?,
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wfc::collapse::collapse;
+    use std::iter::FromIterator;
+
+    fn all_labels() -> MSu16xNU {
+        MSu16xNU::from_iter([2, 3, 1].iter().cloned())
+    }
+
+    fn square_edges() -> Edges {
+        crate::utils::hash_map(&[
+            (0, vec![(1, 0), (3, 2)]),
+            (1, vec![(0, 1), (2, 2)]),
+            (2, vec![(1, 3), (3, 1)]),
+            (3, vec![(2, 0), (0, 3)]),
+        ])
+    }
+
+    #[test]
+    fn test_parse_dot() {
+        let (graph, names) = parse("resources/test/graph_example.dot", &all_labels()).unwrap();
+        assert_eq!(names.keys().collect::<Vec<_>>(), vec!["a", "b", "c", "d"]);
+        assert_eq!(graph.edges, square_edges());
+        assert_eq!(graph.vertices[0], [2, 0, 0].iter().collect());
+        assert_eq!(graph.vertices[1], all_labels());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let (graph, names) = parse("resources/test/graph_example.json", &all_labels()).unwrap();
+        assert_eq!(names.len(), 4);
+        assert_eq!(graph.edges, square_edges());
+        assert_eq!(graph.vertices[0], [2, 0, 0].iter().collect());
+        assert_eq!(graph.vertices[3], all_labels());
+    }
+
+    #[test]
+    fn test_dot_chains_and_comments() {
+        let dot = r#"
+            # comment line
+            strict digraph "line" {
+                rankdir=LR; /* block
+                comment */
+                1 -> 2 -> 3 [color=red, label=4] // trailing comment
+                3 [labels="1 2"]
+                4
+            }
+        "#;
+        let (graph, names) = dot_builder(dot).unwrap().build(&all_labels()).unwrap();
+        assert_eq!(names.keys().collect::<Vec<_>>(), vec!["1", "2", "3", "4"]);
+        assert_eq!(graph.edges.get(&0).unwrap(), &vec![(1, 4)]);
+        assert_eq!(graph.edges.get(&1).unwrap(), &vec![(2, 4)]);
+        assert!(graph.edges.get(&3).unwrap().is_empty());
+        assert_eq!(graph.vertices[2], [0, 3, 1].iter().collect());
+    }
+
+    #[test]
+    fn test_dot_errors() {
+        assert!(dot_builder("graph { a -- b }").is_err());
+        assert!(dot_builder("digraph { a -> b }").is_err());
+        assert!(dot_builder("digraph { a -> b [label=x] }").is_err());
+        assert!(dot_builder("digraph { a -> b [label=1]").is_err());
+        let unknown_label = dot_builder("digraph { a [labels=7] }").unwrap();
+        assert!(unknown_label.build(&all_labels()).is_err());
+    }
+
+    #[test]
+    fn test_json_numeric_names() {
+        let json = r#"{ "edges": [{ "from": 0, "to": 1, "direction": 2 }], "labels": { "1": [2] } }"#;
+        let (graph, names) = json_builder(json).unwrap().build(&all_labels()).unwrap();
+        assert_eq!(names.get("1"), Some(&1));
+        assert_eq!(graph.vertices[1], [0, 0, 1].iter().collect());
+        assert!(json_builder("{ \"edges\": 3 }").is_err());
+    }
+
+    #[test]
+    fn test_collapse_parsed_graph() {
+        let input_graph = Graph::new(
+            vec![
+                [1, 0].iter().collect(),
+                [0, 1].iter().collect(),
+                [1, 0].iter().collect(),
+                [0, 1].iter().collect(),
+            ],
+            square_edges(),
+            [2, 2].iter().collect(),
+        );
+        let all_labels = input_graph.all_labels;
+        let (output_graph, _) = parse("resources/test/graph_example.dot", &all_labels).unwrap();
+        let collapsed = collapse(&input_graph.rules(), &output_graph, Some(1), None);
+        // vertex a is pinned to label 0, so the labels must alternate around the square
+        assert!(collapsed.vertices.iter().all(|labels| labels.is_singleton()));
+        let labels: Vec<usize> = collapsed.vertices.iter().map(|labels| labels.imax()).collect();
+        assert_eq!(labels, vec![0, 1, 0, 1]);
+    }
+}
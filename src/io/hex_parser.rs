@@ -0,0 +1,226 @@
+use crate::graph::graph::Graph;
+use crate::io::text_parser::{rows, token_maps, vertex_token, Tokens, Undecided};
+use crate::io::utils::{make_edges_hex_grid, offset_to_axial, axial_to_offset};
+use crate::utils::{index_to_coords, is_inside, coords_to_index};
+use hashbrown::HashMap;
+use image::{Rgb, RgbImage};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::fs::{read_to_string, write};
+use std::io::Error;
+
+// Hex text samples use offset rows: cells are separated by a space and every odd row is
+// indented by one space, e.g.
+//
+// ~ ~ . .
+//  ~ . ^ .
+// ~ ~ . .
+pub fn parse(filename: &str) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let string = read_to_string(filename)?;
+    // every row must have the same number of cells for the edges to match the vertices
+    let rows = rows(&string, Tokens::Words)?;
+    let edges = make_edges_hex_grid(rows[0].len(), rows.len());
+    let (token_frequency, vertices) = token_maps(rows.concat())?;
+    let all_labels = token_frequency.values().collect();
+    Ok((Graph::new(vertices, edges, all_labels), token_frequency))
+}
+
+pub fn render(
+    filename: &str,
+    graph: Graph,
+    key: &IndexMap<String, u16>,
+    width: usize,
+) {
+    let undecided = Undecided::default();
+    let lines: String = graph
+        .vertices
+        .chunks_exact(width)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let cells: Vec<Cow<str>> = chunk
+                .iter()
+                .map(|labels| vertex_token(labels, key, &undecided))
+                .collect();
+            let indent = if row % 2 == 1 { " " } else { "" };
+            format!("{}{}\n", indent, cells.join(" "))
+        })
+        .collect();
+    if write(filename, lines).is_ok() {}
+}
+
+const CONTRADICT_COLOUR: Rgb<u8> = Rgb([255, 0, 0]);
+const BACKGROUND_COLOUR: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// Render a hex graph as an image of pointy topped hexagons with a circumradius of `cell_size` pixels.
+/// Cells that are not collapsed are drawn with the average colour of their remaining labels.
+pub fn render_image(
+    filename: &str,
+    graph: Graph,
+    key: &IndexMap<String, u16>,
+    palette: &HashMap<String, Rgb<u8>>,
+    width: usize,
+    cell_size: usize,
+) {
+    let depth = graph.vertices.len() / width;
+    let size = cell_size as f64;
+    let root_3 = 3f64.sqrt();
+    let image_width = (root_3 * size * (width as f64 + 0.5)).ceil() as u32;
+    let image_height = (size * (1.5 * depth as f64 + 0.5)).ceil() as u32;
+
+    let colours: Vec<Rgb<u8>> = graph
+        .vertices
+        .iter()
+        .map(|labels| {
+            let label_colours: Vec<Rgb<u8>> = labels
+                .into_iter()
+                .enumerate()
+                .filter(|(_, frequency)| frequency > &0)
+                .filter_map(|(label, _)| key.get_index(label).and_then(|(token, _)| palette.get(token)))
+                .copied()
+                .collect();
+            blend(&label_colours)
+        })
+        .collect();
+
+    let mut output_image: RgbImage = RgbImage::from_pixel(image_width, image_height, BACKGROUND_COLOUR);
+    output_image
+        .enumerate_pixels_mut()
+        .for_each(|(x, y, pixel)| {
+            // project pixel centre into fractional axial coordinates relative to the centre of cell 0
+            let px = x as f64 + 0.5 - root_3 / 2.0 * size;
+            let py = y as f64 + 0.5 - size;
+            let q = (root_3 / 3.0 * px - py / 3.0) / size;
+            let r = (2.0 / 3.0 * py) / size;
+            let (col, row) = axial_to_offset(axial_round(q, r));
+            if is_inside((col, row), (width, depth)) {
+                *pixel = colours[coords_to_index(col as usize, row as usize, width)]
+            }
+        });
+
+    output_image.save(filename).unwrap();
+}
+
+/// The pixel position of the centre of a hex cell.
+pub fn cell_centre(index: usize, width: usize, cell_size: usize) -> (f64, f64) {
+    let (col, row) = index_to_coords(index, width);
+    let (q, r) = offset_to_axial((col as i32, row as i32));
+    let size = cell_size as f64;
+    let x = size * 3f64.sqrt() * (q as f64 + r as f64 / 2.0 + 0.5);
+    let y = size * (1.5 * r as f64 + 1.0);
+    (x, y)
+}
+
+// round fractional axial coordinates to the nearest hex via cube coordinates
+fn axial_round(q: f64, r: f64) -> (i32, i32) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs
+    } else if dr > ds {
+        rr = -rq - rs
+    }
+    (rq as i32, rr as i32)
+}
+
+fn blend(colours: &[Rgb<u8>]) -> Rgb<u8> {
+    if colours.is_empty() {
+        return CONTRADICT_COLOUR
+    }
+    let sum = colours
+        .iter()
+        .fold([0usize; 3], |mut acc, colour| {
+            acc.iter_mut().zip(colour.0.iter()).for_each(|(a, c)| *a += *c as usize);
+            acc
+        });
+    Rgb([
+        (sum[0] / colours.len()) as u8,
+        (sum[1] / colours.len()) as u8,
+        (sum[2] / colours.len()) as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MSu16xNU;
+
+    #[test]
+    fn test_parse_hex() {
+        let (graph, keys) = parse("resources/test/hex_sample.txt").unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(graph.vertices.len(), 36);
+        // interior cells have six neighbours
+        assert_eq!(graph.edges.get(&14).unwrap().len(), 6);
+        // hex rules only use the six hex directions
+        assert!(graph.rules().keys().all(|(direction, _)| *direction < 6));
+    }
+
+    #[test]
+    fn test_parse_hex_ragged_rows() {
+        let filename = "resources/test/hex_ragged_test.txt";
+        write(filename, "~ ~ . .\n ~ . ^\n~ ~ . .\n").unwrap();
+        let parsed = parse(filename);
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(parsed.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_axial_round() {
+        assert_eq!(axial_round(0.1, 0.2), (0, 0));
+        assert_eq!(axial_round(0.9, -0.1), (1, 0));
+        assert_eq!(axial_round(-0.6, 0.9), (-1, 1));
+        // rounding each axis independently would give (0, 0) here
+        assert_eq!(axial_round(0.45, 0.4), (1, 0));
+    }
+
+    #[test]
+    fn test_cell_centre() {
+        let (x, y) = cell_centre(0, 3, 10);
+        assert!((x - 3f64.sqrt() * 5.0).abs() < 1e-9);
+        assert!((y - 10.0).abs() < 1e-9);
+
+        // odd rows are shifted by half a cell
+        let (x, y) = cell_centre(3, 3, 10);
+        assert!((x - 3f64.sqrt() * 10.0).abs() < 1e-9);
+        assert!((y - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_blend() {
+        let colours = vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+        assert_eq!(blend(&colours), Rgb([127, 127, 127]));
+        assert_eq!(blend(&[]), CONTRADICT_COLOUR);
+    }
+
+    #[test]
+    fn test_render_image_pixels() {
+        let mut key: IndexMap<String, u16> = IndexMap::new();
+        key.insert("a".to_string(), 1);
+        key.insert("b".to_string(), 1);
+        let mut palette = HashMap::new();
+        palette.insert("a".to_string(), Rgb([0, 0, 255]));
+        palette.insert("b".to_string(), Rgb([0, 255, 0]));
+
+        let mut a = MSu16xNU::empty();
+        a.insert(0, 1);
+        let mut b = MSu16xNU::empty();
+        b.insert(1, 1);
+        let all_labels: MSu16xNU = [1, 1].iter().collect();
+        let graph = Graph::new(vec![a, b, b, a], make_edges_hex_grid(2, 2), all_labels);
+
+        let filename = "resources/test/hex_render_test.png";
+        render_image(filename, graph, &key, &palette, 2, 10);
+        let image = image::open(filename).unwrap().to_rgb8();
+        std::fs::remove_file(filename).unwrap();
+
+        let pixel_at = |index: usize| {
+            let (x, y) = cell_centre(index, 2, 10);
+            *image.get_pixel(x as u32, y as u32)
+        };
+        assert_eq!(pixel_at(0), Rgb([0, 0, 255]));
+        assert_eq!(pixel_at(1), Rgb([0, 255, 0]));
+        assert_eq!(pixel_at(2), Rgb([0, 255, 0]));
+        assert_eq!(pixel_at(3), Rgb([0, 0, 255]));
+    }
+}
@@ -1,8 +1,15 @@
 pub mod text_parser;
+pub mod hex_parser;
+pub mod voxel_parser;
+pub mod graph_parser;
+pub mod dot_renderer;
+pub mod persist;
+pub mod rule_file;
 pub mod tri_wave;
 pub mod limit_iter;
 pub mod sub_matrix;
 mod frame_padder;
 pub mod olm;
+pub mod tiled;
 pub mod post_processors;
 pub mod utils;
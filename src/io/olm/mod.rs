@@ -1,2 +1,3 @@
 pub mod olm_parser;
-pub mod olm_renderer;
\ No newline at end of file
+pub mod olm_text_parser;
+pub mod olm_renderer;
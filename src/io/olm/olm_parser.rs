@@ -1,4 +1,4 @@
-use crate::graph::graph::{Rules, Edges, Graph, Vertices};
+use crate::graph::graph::{Rules, Edges, Graph};
 use crate::io::{
     limit_iter::Limit,
     sub_matrix::SubMatrix,
@@ -6,29 +6,105 @@ use crate::io::{
     utils::{DiagonalReflection, Reflection, Rotation},
 };
 use crate::utils::{index_to_coords, is_inside, coords_to_index};
-use crate::wfc::collapse;
+use crate::wfc::collapse::{self, build_constraint};
 
 use bimap::BiMap;
 use hashbrown::HashMap;
-use image::{imageops, Rgb, RgbImage, Pixel};
+use image::{Rgba, RgbaImage};
 use itertools::Itertools;
 use nalgebra::DMatrix;
-use std::ops::{IndexMut, Index, AddAssign};
+use rayon::prelude::*;
+use std::ops::{IndexMut, Index};
 use std::convert::TryFrom;
 use indexmap::IndexMap;
-use std::ops::Not;
 
 use crate::MSu16xNU;
 
-type Chunk = DMatrix<usize>;
-type PixelKeys = BiMap<usize, Rgb<u8>>;
+pub type Chunk = DMatrix<usize>;
+pub type PixelKeys = BiMap<usize, Rgba<u8>>;
+// (width, height) of a chunk in pixels
+pub type ChunkSize = (usize, usize);
+
+/// Transformed copies of every chunk added to the chunks of a sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSymmetry {
+    /// Add the chunk rotated by 90, 180 and 270 degrees.
+    pub rotate: bool,
+    /// Add the chunk flipped top to bottom.
+    pub reflect_vertical: bool,
+    /// Add the chunk mirrored left to right.
+    pub reflect_horizontal: bool,
+    /// Add the chunk reflected in both of its diagonals.
+    pub reflect_diagonal: bool,
+}
+
+impl ChunkSymmetry {
+    /// Only the chunks found in the sample.
+    pub fn none() -> ChunkSymmetry {
+        ChunkSymmetry { rotate: false, reflect_vertical: false, reflect_horizontal: false, reflect_diagonal: false }
+    }
+
+    /// Every rotation and reflection of each chunk.
+    pub fn all() -> ChunkSymmetry {
+        ChunkSymmetry { rotate: true, reflect_vertical: true, reflect_horizontal: true, reflect_diagonal: true }
+    }
+}
+
+impl Default for ChunkSymmetry {
+    fn default() -> Self {
+        ChunkSymmetry { rotate: true, ..ChunkSymmetry::none() }
+    }
+}
+
+/// How the rules between chunks placed side by side are found from the overlaps of the chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pruning {
+    /// Propagate the overlaps of each chunk through a graph of its neighbourhood, strict but slow.
+    #[default]
+    Graph,
+    /// Only check the positions overlapping both chunks, fast but keeps a few more rules.
+    Direct,
+}
 
 // TODO: handle unwrap of image::open properly
-pub fn parse(filename: &str, chunk_size: usize) -> (Rules, PixelKeys, MSu16xNU, IndexMap<Chunk, u16>) {
-    let img = image::open(filename).unwrap().to_rgb8();
-    let pixel_aliases = alias_pixels(&img);
-    let chunk_frequencies = chunk_image(img, chunk_size, &pixel_aliases, true, false, false, false);
-    let overlap_rules = overlaps(&chunk_frequencies, chunk_size);
+pub fn parse(
+    filename: &str,
+    chunk_size: ChunkSize,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
+    pruning: Pruning,
+) -> (Rules, PixelKeys, MSu16xNU, IndexMap<Chunk, u16>) {
+    parse_multiple(&[filename], chunk_size, symmetry, periodic, pruning)
+}
+
+/// Parse several sample images into one set of rules.
+/// Pixels are aliased across all of the images and the chunk frequencies of every image are summed,
+/// so labels refer to the same chunks whichever sample they came from.
+/// Periodic samples are tileable, so chunks crossing their edges wrap around to the opposite edge.
+/// Chunks that are not square only use the symmetries that keep their shape.
+pub fn parse_multiple(
+    filenames: &[&str],
+    chunk_size: ChunkSize,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
+    pruning: Pruning,
+) -> (Rules, PixelKeys, MSu16xNU, IndexMap<Chunk, u16>) {
+    let images: Vec<RgbaImage> = filenames
+        .iter()
+        .map(|filename| image::open(filename).unwrap().to_rgba8())
+        .collect();
+    let pixel_aliases = alias_images(&images);
+    let chunk_frequencies = images
+        .into_iter()
+        .map(|img| chunk_image(img, chunk_size, &pixel_aliases, symmetry, periodic))
+        .fold(IndexMap::new(), |mut acc: IndexMap<Chunk, u16>, frequencies| {
+            frequencies
+                .into_iter()
+                .for_each(|(chunk, frequency)| {
+                    acc.entry(chunk).and_modify(|f| *f = f.saturating_add(frequency)).or_insert(frequency);
+                });
+            acc
+        });
 
     if chunk_frequencies.len() > MSu16xNU::len() {
         println!("Chunks LEN: {}", chunk_frequencies.len());
@@ -36,69 +112,147 @@ pub fn parse(filename: &str, chunk_size: usize) -> (Rules, PixelKeys, MSu16xNU,
     }
 
     let all_labels = chunk_frequencies.values().collect();
-    let raw_graph = create_raw_graph(&all_labels, chunk_size, (3, 3));
-    let mut pruned_rules: Rules = HashMap::new();
+    let pruned_rules = chunk_rules(&all_labels, &chunk_frequencies, chunk_size, pruning);
+
+    (pruned_rules, pixel_aliases, all_labels, chunk_frequencies)
+}
+
+/// Rules between chunks placed side by side, found from the overlaps of the chunks.
+pub(crate) fn chunk_rules(
+    all_labels: &MSu16xNU,
+    chunk_frequencies: &IndexMap<Chunk, u16>,
+    chunk_size: ChunkSize,
+    pruning: Pruning,
+) -> Rules {
+    match pruning {
+        Pruning::Graph => graph_chunk_rules(all_labels, chunk_frequencies, chunk_size),
+        Pruning::Direct => direct_chunk_rules(all_labels, chunk_frequencies, chunk_size),
+    }
+}
+
+// found by pruning a graph of overlapping chunks, each label in its own copy of the graph in parallel
+fn graph_chunk_rules(
+    all_labels: &MSu16xNU,
+    chunk_frequencies: &IndexMap<Chunk, u16>,
+    chunk_size: ChunkSize,
+) -> Rules {
+    let overlap_rules = overlaps(chunk_frequencies, chunk_size);
+    let raw_graph = create_raw_graph(all_labels, chunk_size, (3, 3));
 
     (0..all_labels.count_non_zero())
-        .for_each(|label| {
+        .into_par_iter()
+        .flat_map_iter(|label| {
             // pruned graph vertices returned from collapse
-            let pruned_graph = propagate_overlaps(raw_graph.clone(), &overlap_rules, label as usize);
+            let pruned_graph = propagate_overlaps(raw_graph.clone(), &overlap_rules, label);
 
             real_vertex_indexes(chunk_size)
                 .iter()
                 .enumerate()
-                .for_each(|(direction, index)| {
-                    let set = pruned_graph.vertices.index(*index);
-                    if !set.is_empty() {
-                        pruned_rules.insert((direction as u16, label as usize), *set);
-                    }
-                });
-        });
-
-    (pruned_rules, pixel_aliases, all_labels, chunk_frequencies)
+                .map(|(direction, index)| ((direction as u16, label), *pruned_graph.vertices.index(*index)))
+                .filter(|(_, set)| !set.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
 }
 
-// todo: work out if step will be needed, currently useless
-const fn real_vertex_indexes(chunk_size: usize) -> [usize; 8] {
-    let dim = (3 * chunk_size) - (chunk_size - 1);
-    let step = chunk_size - 1;
-    [
-        0,                                      // NW
-        step + 1,                               // N
-        (step + 1) * 2,                         // NE
-        dim * chunk_size,                       // W
-        // dim * chunk_size + step + 1          // Center (unused)
-        dim * chunk_size + (step + 1) * 2,      // E
-        dim * chunk_size * 2,                   // SW
-        dim * chunk_size * 2 + step + 1,        // S
-        dim * chunk_size * 2 + (step + 1) * 2,  // SE
-    ]
-}
+// computed straight from the overlaps without a graph. A chunk is allowed next to another when every
+// position overlapping both of them can hold a chunk overlapping each of them. Unless the graph meets
+// a contradiction, every rule it keeps is kept here too, along with those only failing further away.
+fn direct_chunk_rules(
+    all_labels: &MSu16xNU,
+    chunk_frequencies: &IndexMap<Chunk, u16>,
+    chunk_size: ChunkSize,
+) -> Rules {
+    let overlap_rules = overlaps(chunk_frequencies, chunk_size);
+    let (chunk_width, chunk_height) = chunk_size;
+    let (signed_width, signed_height) = (chunk_width as i32, chunk_height as i32);
+
+    // pixel offsets of the 8 neighbouring chunks, in direction order
+    let neighbours: Vec<(i32, i32)> = (-1..=1)
+        .cartesian_product(-1..=1)
+        .filter(|offset| offset != &(0, 0))
+        .map(|(y, x)| (x * signed_width, y * signed_height))
+        .collect();
 
-fn sub_images(image: RgbImage, chunk_size: usize) -> impl Iterator<Item=RgbImage> {
-    let chunk_size_32: u32 = TryFrom::try_from(chunk_size)
-        .expect("chunk_size too large, cannot convert to u32");
+    (0..all_labels.count_non_zero())
+        .into_par_iter()
+        .flat_map_iter(|label| {
+            neighbours
+                .iter()
+                .enumerate()
+                .map(|(direction, (x, y))| {
+                    // offsets overlapping both this chunk and the neighbouring chunk
+                    let between = (1 - signed_height..signed_height)
+                        .cartesian_product(1 - signed_width..signed_width)
+                        .filter(|&(o_y, o_x)| (y - o_y).abs() < signed_height && (x - o_x).abs() < signed_width)
+                        .filter(|&(o_y, o_x)| (o_x, o_y) != (0, 0));
+
+                    let set = between.fold(*all_labels, |set, (o_y, o_x)| {
+                        let overlapping = overlap_rules
+                            .get(&(overlap_direction((o_x, o_y), chunk_size), label))
+                            .copied()
+                            .unwrap_or_else(MSu16xNU::empty);
+                        let reachable = build_constraint(
+                            &overlapping,
+                            overlap_direction((x - o_x, y - o_y), chunk_size),
+                            &overlap_rules
+                        );
+                        set.intersection(&reachable)
+                    });
+                    ((direction as u16, label), set)
+                })
+                .filter(|(_, set)| !set.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
 
-    let height_iter = 0..(image.dimensions().1) - (chunk_size_32 - 1);
-    let width_iter = 0..(image.dimensions().0) - (chunk_size_32 - 1);
+// overlap rule direction of a pixel offset, matching the order of `sub_chunk_positions`
+fn overlap_direction((x, y): (i32, i32), (chunk_width, chunk_height): ChunkSize) -> u16 {
+    let row = (y + chunk_height as i32 - 1) as usize;
+    let column = (x + chunk_width as i32 - 1) as usize;
+    let index = row * (2 * chunk_width - 1) + column;
+    let centre = (chunk_height - 1) * (2 * chunk_width - 1) + chunk_width - 1;
+    match index > centre {
+        true => (index - 1) as u16,
+        false => index as u16
+    }
+}
 
-    height_iter
-        .cartesian_product(width_iter)
-        .map(move |(y, x)| {
-            imageops::crop_imm(&image, x, y, chunk_size_32, chunk_size_32).to_image()
-        })
+// indexes of the vertices holding the 8 chunks around the central chunk of the raw graph
+fn real_vertex_indexes((chunk_width, chunk_height): ChunkSize) -> [usize; 8] {
+    let dim = 2 * chunk_width + 1;
+    let index = |x: usize, y: usize| coords_to_index(x * chunk_width, y * chunk_height, dim);
+    [
+        index(0, 0), // NW
+        index(1, 0), // N
+        index(2, 0), // NE
+        index(0, 1), // W
+        // index(1, 1) Center (unused)
+        index(2, 1), // E
+        index(0, 2), // SW
+        index(1, 2), // S
+        index(2, 2), // SE
+    ]
 }
 
-fn alias_sub_image(image: RgbImage, pixel_aliases: &PixelKeys) -> Vec<usize> {
-    image
+fn alias_image(image: &RgbaImage, pixel_aliases: &PixelKeys) -> DMatrix<usize> {
+    let (width, height) = image.dimensions();
+    let aliases: Vec<usize> = image
         .pixels()
-        .map(|p| *pixel_aliases.get_by_right(&p).unwrap())
-        .collect()
+        .map(|p| *pixel_aliases.get_by_right(p).unwrap())
+        .collect();
+    DMatrix::from_row_slice(height as usize, width as usize, &aliases)
 }
 
-fn alias_pixels(image: &RgbImage) -> PixelKeys {
-    image
-        .pixels()
+fn alias_images(images: &[RgbaImage]) -> PixelKeys {
+    images
+        .iter()
+        .flat_map(|image| image.pixels())
         .unique()
         .copied()
         .enumerate()
@@ -107,33 +261,53 @@ fn alias_pixels(image: &RgbImage) -> PixelKeys {
 
 // returns the input image in unique chunks and frequencies of those chunks
 fn chunk_image(
-    image: RgbImage,
-    chunk_size: usize,
+    image: RgbaImage,
+    chunk_size: ChunkSize,
     pixel_aliases: &PixelKeys,
-    rotate: bool,
-    reflect_vertical: bool,
-    reflect_horizontal: bool,
-    reflect_diagonal: bool,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
 ) -> IndexMap<Chunk, u16> {
-    sub_images(image, chunk_size)
-        .map(|sub_image| alias_sub_image(sub_image, pixel_aliases))
-        .fold(IndexMap::new(), |mut acc, aliases| {
-            let chunk = DMatrix::from_row_slice(chunk_size, chunk_size, &aliases);
+    let aliases = alias_image(&image, pixel_aliases);
+    chunk_aliases(&aliases, chunk_size, symmetry, periodic)
+}
 
-            if rotate {
+// returns a matrix of aliases in unique chunks and frequencies of those chunks
+pub(crate) fn chunk_aliases(
+    aliases: &DMatrix<usize>,
+    (chunk_width, chunk_height): ChunkSize,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
+) -> IndexMap<Chunk, u16> {
+    let (rows, columns) = aliases.shape();
+    // every position of a periodic sample starts a chunk, wrapping around its edges
+    let (height, width) = match periodic {
+        true => (rows, columns),
+        false => ((rows + 1).saturating_sub(chunk_height), (columns + 1).saturating_sub(chunk_width))
+    };
+    // quarter turns and diagonal reflections would change the shape of a rectangular chunk
+    let square = chunk_width == chunk_height;
+
+    (0..height)
+        .cartesian_product(0..width)
+        .map(|(y, x)| DMatrix::from_fn(chunk_height, chunk_width, |i, j| aliases[((y + i) % rows, (x + j) % columns)]))
+        .fold(IndexMap::new(), |mut acc, chunk| {
+            if symmetry.rotate && !square {
+                push_chunk_frequency(chunk.rotate_90().rotate_90(), &mut acc);
+            }
+            if symmetry.rotate && square {
                 let mut rot_chunk = chunk.clone();
                 for _ in 0..3 {
                     rot_chunk = rot_chunk.rotate_90();
                     push_chunk_frequency(rot_chunk.clone(), &mut acc);
                 }
             }
-            if reflect_vertical {
+            if symmetry.reflect_vertical {
                 push_chunk_frequency(chunk.reflect_vertical(), &mut acc);
             }
-            if reflect_horizontal {
+            if symmetry.reflect_horizontal {
                 push_chunk_frequency(chunk.reflect_horizontal(), &mut acc);
             }
-            if reflect_diagonal {
+            if symmetry.reflect_diagonal && square {
                 push_chunk_frequency(chunk.reflect_top_left(), &mut acc);
                 push_chunk_frequency(chunk.reflect_bottom_left(), &mut acc);
             }
@@ -151,17 +325,16 @@ type Position = (usize, usize);
 type Size = (usize, usize);
 type Direction = u16;
 
-fn sub_chunk_positions(chunk_size: usize) -> Vec<(Position, Size, Direction)> {
-    let period = chunk_size * 2 - 1;
-    let positions = Limit::new(chunk_size).zip(TriWave::new(chunk_size)).take(period);
-    let pos_cart_prod = positions.clone().cartesian_product(positions);
+fn sub_chunk_positions((chunk_width, chunk_height): ChunkSize) -> Vec<(Position, Size, Direction)> {
+    let positions = |length: usize| Limit::new(length).zip(TriWave::new(length)).take(length * 2 - 1);
+    let pos_cart_prod = positions(chunk_height).cartesian_product(positions(chunk_width));
 
     pos_cart_prod
         .map(|((y_position, y_size), (x_position, x_size))| (
             (x_position, y_position),
             (x_size + 1, y_size + 1)
         ))
-        .filter(|(_, (width, height))| width != &chunk_size || height != &chunk_size)
+        .filter(|(_, (width, height))| width != &chunk_width || height != &chunk_height)
         .enumerate()
         .map(|(direction, (position, size))| (
             position,
@@ -171,54 +344,66 @@ fn sub_chunk_positions(chunk_size: usize) -> Vec<(Position, Size, Direction)> {
         .collect()
 }
 
-fn overlaps(chunks: &IndexMap<Chunk, u16>, chunk_size: usize) -> Rules {
+fn overlaps(chunks: &IndexMap<Chunk, u16>, chunk_size: ChunkSize) -> Rules {
+    let sub_positions = sub_chunk_positions(chunk_size);
+
+    // for each sub position, the labels of the chunks sharing each sub chunk found there
+    let sub_chunk_index: Vec<HashMap<Chunk, MSu16xNU>> = sub_positions
+        .iter()
+        .map(|(position, size, _)| {
+            chunks
+                .keys()
+                .enumerate()
+                .fold(HashMap::new(), |mut index, (label, chunk)| {
+                    index
+                        .entry(chunk.sub_matrix(*position, *size))
+                        .or_insert_with(MSu16xNU::empty)
+                        .insert(label, 1);
+                    index
+                })
+        })
+        .collect();
+
     chunks
         .keys()
         .enumerate()
         .fold(HashMap::new(), |mut rules, (label, chunk)| {
-            let sub_positions = sub_chunk_positions(chunk_size);
             sub_positions
                 .iter()
                 .for_each(|(position, size, direction)| {
                     let sub_chunk = chunk.sub_matrix(*position, *size);
+                    // find mirrored sub chunk
                     let reverse_index = sub_positions.len() - 1 - *direction as usize;
-                    let (rev_pos, rev_size, _) = sub_positions[reverse_index];
-                    chunks
-                        .keys()
-                        .enumerate()
-                        .for_each(|(other_label, other_chunk)| {
-                            // find mirrored sub chunk
-                            let other_sub_chunk = other_chunk.sub_matrix(rev_pos, rev_size);
-                            if sub_chunk == other_sub_chunk {
-                                let mut set = MSu16xNU::empty();
-                                set.insert(other_label, 1);
-                                rules
-                                    .entry((*direction, label))
-                                    .and_modify(|l| l.add_assign(set))
-                                    .or_insert(set);
-                            }
-                        })
+                    if let Some(set) = sub_chunk_index[reverse_index].get(&sub_chunk) {
+                        rules.insert((*direction, label), *set);
+                    }
                 });
             rules
         })
 }
 
 // Create a raw graph for pruning
-fn create_raw_graph(all_labels: &MSu16xNU, chunk_size: usize, (height, width): (usize, usize)) -> Graph {
+fn create_raw_graph(
+    all_labels: &MSu16xNU,
+    (chunk_width, chunk_height): ChunkSize,
+    (height, width): (usize, usize)
+) -> Graph {
     // pixel based graph dimensions
-    let v_dim_x = (width * chunk_size) - (chunk_size - 1);
-    let v_dim_y = (height * chunk_size) - (chunk_size - 1);
+    let v_dim_x = (width * chunk_width) - (chunk_width - 1);
+    let v_dim_y = (height * chunk_height) - (chunk_height - 1);
     let vertices_len = v_dim_x * v_dim_y;
     let vertices: Vec<MSu16xNU> = vec![*all_labels; vertices_len];
 
-    // create negative indexed range to offset vertex centered directional field by N
-    let signed_chunk_size: i32 = TryFrom::try_from(chunk_size)
-        .expect("Cannot convert chunk_size to i32");
-    let range = 1 - signed_chunk_size..signed_chunk_size;
+    // create negative indexed ranges to offset vertex centered directional field by the chunk size
+    let range = |length: usize| {
+        let signed_length: i32 = TryFrom::try_from(length)
+            .expect("Cannot convert chunk_size to i32");
+        1 - signed_length..signed_length
+    };
 
     // calculate real cartesian space offest coordinates
-    let range_cart_prod = range.clone()
-        .cartesian_product(range)
+    let range_cart_prod = range(chunk_height)
+        .cartesian_product(range(chunk_width))
         .filter(|i| i != &(0, 0)); // remove 0 offset for correct directional mapping
 
     let edges: Edges = (0..vertices_len)
@@ -229,7 +414,7 @@ fn create_raw_graph(all_labels: &MSu16xNU, chunk_size: usize, (height, width): (
                 .map(|(y_offset, x_offset)| (y as i32 + y_offset, x as i32 + x_offset))
                 .enumerate()
                 // remove coordinates outside of graph
-                .filter(|(_, offsets)| is_inside(*offsets, (v_dim_x, v_dim_y)))
+                .filter(|(_, (y_offset, x_offset))| is_inside((*x_offset, *y_offset), (v_dim_x, v_dim_y)))
                 .for_each(|(direction, (y_offset, x_offset))| {
                     let other_index = coords_to_index(x_offset as usize, y_offset as usize, v_dim_x);
                     acc
@@ -257,20 +442,59 @@ mod tests {
 
     #[test]
     fn test_alias_pixels() {
-        let pixels = vec![255, 255, 255, 0, 0, 0, 122, 122, 122, 96, 96, 96];
+        let pixels = vec![255, 255, 255, 255, 0, 0, 0, 255, 122, 122, 122, 255, 96, 96, 96, 255];
         let img = ImageBuffer::from_vec(2, 2, pixels).unwrap();
-        let pixel_aliases = alias_pixels(&img);
+        let pixel_aliases = alias_images(&[img]);
         assert_eq!(pixel_aliases.len(), 4);
     }
 
+    #[test]
+    fn test_alias_images() {
+        let pixels_a = vec![255, 255, 255, 255, 0, 0, 0, 255, 122, 122, 122, 255, 0, 0, 0, 255];
+        let pixels_b = vec![0, 0, 0, 255, 1, 2, 3, 255, 255, 255, 255, 255, 4, 5, 6, 255];
+        let img_a = ImageBuffer::from_vec(2, 2, pixels_a).unwrap();
+        let img_b = ImageBuffer::from_vec(2, 2, pixels_b).unwrap();
+        let pixel_aliases = alias_images(&[img_a, img_b]);
+        assert_eq!(pixel_aliases.len(), 5);
+        assert_eq!(pixel_aliases.get_by_left(&0), Some(&Rgba([255, 255, 255, 255])));
+        assert_eq!(pixel_aliases.get_by_left(&3), Some(&Rgba([1, 2, 3, 255])));
+    }
+
+    #[test]
+    fn test_alias_transparent_pixels() {
+        // opaque black, transparent black and half transparent black are all distinct
+        let pixels = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 128, 0, 0, 0, 0];
+        let img = ImageBuffer::from_vec(2, 2, pixels).unwrap();
+        let pixel_aliases = alias_images(&[img]);
+        assert_eq!(pixel_aliases.len(), 3);
+        assert_eq!(pixel_aliases.get_by_left(&1), Some(&Rgba([0, 0, 0, 0])));
+    }
+
+    #[test]
+    fn test_parse_multiple() {
+        let filename = "resources/test/chunk_image_test.png";
+        let symmetry = ChunkSymmetry::default();
+        let (_, single_keys, single_labels, single_chunks) = parse(filename, (2, 2), &symmetry, false, Pruning::Graph);
+        let (_, keys, all_labels, chunks) = parse_multiple(&[filename, filename], (2, 2), &symmetry, false, Pruning::Graph);
+
+        // the same chunks and pixels are found, at twice the frequency
+        assert_eq!(keys, single_keys);
+        assert_eq!(chunks.keys().collect::<Vec<_>>(), single_chunks.keys().collect::<Vec<_>>());
+        chunks
+            .iter()
+            .for_each(|(chunk, frequency)| assert_eq!(*frequency, single_chunks.get(chunk).unwrap() * 2));
+        assert_eq!(all_labels, single_chunks.values().map(|f| f * 2).collect());
+        assert_eq!(single_labels, single_chunks.values().collect());
+    }
+
     #[test]
     fn test_chunk_image() {
-        let img = image::open("resources/test/chunk_image_test.png").unwrap().to_rgb8();
+        let img = image::open("resources/test/chunk_image_test.png").unwrap().to_rgba8();
         let mut pixel_aliases: PixelKeys = BiMap::new();
-        pixel_aliases.insert(0, Rgb::from([255, 255, 255]));
-        pixel_aliases.insert(1, Rgb::from([0, 0, 0]));
+        pixel_aliases.insert(0, Rgba([255, 255, 255, 255]));
+        pixel_aliases.insert(1, Rgba([0, 0, 0, 255]));
 
-        let chunk_map = chunk_image(img, 2, &pixel_aliases, true, false, false, false);
+        let chunk_map = chunk_image(img, (2, 2), &pixel_aliases, &ChunkSymmetry::default(), false);
 
         let mut expected_map: IndexMap<Chunk, u16> = IndexMap::new();
         expected_map.insert(DMatrix::from_row_slice(2, 2, &vec![1, 0, 0, 0]), 1);
@@ -290,6 +514,69 @@ mod tests {
             });
     }
 
+    #[test]
+    fn test_chunk_symmetries() {
+        // a single chunk with no symmetry of its own, so every transform of it is distinct
+        let aliases = DMatrix::from_row_slice(2, 2, &[0, 1, 2, 3]);
+        let symmetry_chunks = |symmetry: ChunkSymmetry| chunk_aliases(&aliases, (2, 2), &symmetry, false);
+        let none = ChunkSymmetry::none();
+
+        assert_eq!(symmetry_chunks(none).keys().collect::<Vec<_>>(), vec![&aliases]);
+
+        let rotations = symmetry_chunks(ChunkSymmetry { rotate: true, ..none });
+        assert_eq!(rotations.len(), 4);
+        assert!(rotations.contains_key(&DMatrix::from_row_slice(2, 2, &[3, 2, 1, 0])));
+
+        let vertical = symmetry_chunks(ChunkSymmetry { reflect_vertical: true, ..none });
+        let flipped = DMatrix::from_row_slice(2, 2, &[2, 3, 0, 1]);
+        assert_eq!(vertical.keys().collect::<Vec<_>>(), vec![&flipped, &aliases]);
+
+        let horizontal = symmetry_chunks(ChunkSymmetry { reflect_horizontal: true, ..none });
+        let mirrored = DMatrix::from_row_slice(2, 2, &[1, 0, 3, 2]);
+        assert_eq!(horizontal.keys().collect::<Vec<_>>(), vec![&mirrored, &aliases]);
+
+        let diagonal = symmetry_chunks(ChunkSymmetry { reflect_diagonal: true, ..none });
+        assert_eq!(diagonal.len(), 3);
+        assert!(diagonal.contains_key(&aliases.transpose()));
+
+        // together they make the 8 symmetries of the square, each seen once
+        let all = symmetry_chunks(ChunkSymmetry::all());
+        assert_eq!(all.len(), 8);
+        assert!(all.values().all(|frequency| *frequency == 1));
+    }
+
+    #[test]
+    fn test_periodic_chunks() {
+        let aliases = DMatrix::from_row_slice(2, 3, &[0, 1, 2, 3, 4, 5]);
+        let none = ChunkSymmetry::none();
+
+        assert_eq!(chunk_aliases(&aliases, (2, 2), &none, false).len(), 2);
+        // a chunk starts at every position, those crossing the edges wrap around
+        let periodic = chunk_aliases(&aliases, (2, 2), &none, true);
+        assert_eq!(periodic.values().sum::<u16>(), 6);
+        assert!(periodic.contains_key(&DMatrix::from_row_slice(2, 2, &[5, 3, 2, 0])));
+
+        // each pixel of a periodic sample appears in chunk_size squared chunks
+        let img = image::open("resources/test/3Bricks.png").unwrap().to_rgba8();
+        let pixel_aliases = alias_images(&[img.clone()]);
+        let aliases = alias_image(&img, &pixel_aliases);
+        let chunks = chunk_aliases(&aliases, (3, 3), &none, true);
+        assert_eq!(chunks.values().map(|f| *f as usize).sum::<usize>(), aliases.len());
+        let count_pixels = |pixels: Vec<(&usize, usize)>| {
+            pixels
+                .into_iter()
+                .fold(vec![0; pixel_aliases.len()], |mut counts, (alias, count)| {
+                    counts[*alias] += count;
+                    counts
+                })
+        };
+        let chunk_pixels = chunks
+            .iter()
+            .flat_map(|(chunk, frequency)| chunk.iter().map(move |alias| (alias, *frequency as usize)))
+            .collect();
+        assert_eq!(count_pixels(chunk_pixels), count_pixels(aliases.iter().map(|alias| (alias, 9)).collect()));
+    }
+
     #[test]
     fn test_subchunk_positions() {
         let sub_chunks = vec![
@@ -303,7 +590,82 @@ mod tests {
             ((0, 1), (2, 1), 6),
             ((1, 1), (1, 1), 7)
         ];
-        assert_eq!(sub_chunk_positions(2), sub_chunks);
+        assert_eq!(sub_chunk_positions((2, 2)), sub_chunks);
+    }
+
+    #[test]
+    fn test_direct_chunk_rules() {
+        // overlap directions follow the offsets of the sub chunks they share
+        assert_eq!(overlap_direction((-1, -1), (2, 2)), 0);
+        assert_eq!(overlap_direction((1, 0), (2, 2)), 4);
+        assert_eq!(overlap_direction((2, 1), (3, 2)), 13);
+        assert_eq!(overlap_direction((-2, 0), (3, 2)), 5);
+
+        // a stripe repeating every 3 pixels prunes nothing, both only allow the same chunk on every side
+        let stripes = DMatrix::from_row_slice(2, 6, &[0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        let chunks = chunk_aliases(&stripes, (3, 2), &ChunkSymmetry::none(), true);
+        let all_labels: MSu16xNU = chunks.values().collect();
+        let direct_rules = chunk_rules(&all_labels, &chunks, (3, 2), Pruning::Direct);
+        assert_eq!(direct_rules, chunk_rules(&all_labels, &chunks, (3, 2), Pruning::Graph));
+        assert_eq!(direct_rules.len(), 8 * 3);
+        assert!(direct_rules.iter().all(|((_, label), set)| set.is_singleton() && set.imax() == *label));
+
+        // [1 1; 0 0] east of [0 1; 1 1] only fails further away, so it is the one extra direct rule there
+        let sample = DMatrix::from_row_slice(3, 3, &[0, 0, 1, 0, 1, 1, 1, 1, 0]);
+        let chunks = chunk_aliases(&sample, (2, 2), &ChunkSymmetry::none(), true);
+        assert_eq!(chunks.get_index(1).unwrap().0, &DMatrix::from_row_slice(2, 2, &[0, 1, 1, 1]));
+        assert_eq!(chunks.get_index(5).unwrap().0, &DMatrix::from_row_slice(2, 2, &[1, 1, 0, 0]));
+        let all_labels: MSu16xNU = chunks.values().collect();
+        let graph_rules = chunk_rules(&all_labels, &chunks, (2, 2), Pruning::Graph);
+        let direct_rules = chunk_rules(&all_labels, &chunks, (2, 2), Pruning::Direct);
+        assert_eq!(graph_rules.get(&(4, 1)), Some(&[1, 0, 0, 0, 1].iter().collect()));
+        assert_eq!(direct_rules.get(&(4, 1)), Some(&[1, 0, 0, 0, 1, 1].iter().collect()));
+
+        // without contradictions pruning through the whole graph can only remove more
+        let symmetry = ChunkSymmetry::default();
+        let (rules, _, _, _) = parse("resources/test/chunk_image_test.png", (2, 2), &symmetry, false, Pruning::Graph);
+        let (direct_rules, _, _, _) = parse("resources/test/chunk_image_test.png", (2, 2), &symmetry, false, Pruning::Direct);
+        rules
+            .iter()
+            .for_each(|(key, set)| assert!(set.is_subset(direct_rules.get(key).unwrap())));
+    }
+
+    #[test]
+    fn test_rectangular_chunks() {
+        // every offset of a 3 by 2 chunk except the full overlap, rows first
+        let sub_chunks = sub_chunk_positions((3, 2));
+        assert_eq!(sub_chunks.len(), 14);
+        assert_eq!(sub_chunks[2], ((0, 0), (3, 1), 2));
+        assert_eq!(sub_chunks[6], ((0, 0), (2, 2), 6));
+        assert_eq!(sub_chunks[7], ((1, 0), (2, 2), 7));
+        assert_eq!(sub_chunks[13], ((2, 1), (1, 1), 13));
+        sub_chunks
+            .iter()
+            .for_each(|(_, size, direction)| assert_eq!(size, &sub_chunks[13 - *direction as usize].1));
+
+        assert_eq!(real_vertex_indexes((2, 2)), [0, 2, 4, 10, 14, 20, 22, 24]);
+        assert_eq!(real_vertex_indexes((3, 2)), [0, 3, 6, 14, 20, 28, 31, 34]);
+
+        // only the symmetries keeping the shape of the chunk are used
+        let aliases = DMatrix::from_row_slice(2, 3, &[0, 1, 2, 3, 4, 5]);
+        let chunks = chunk_aliases(&aliases, (3, 2), &ChunkSymmetry::all(), false);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.keys().all(|chunk| chunk.shape() == (2, 3)));
+        assert!(chunks.contains_key(&DMatrix::from_row_slice(2, 3, &[5, 4, 3, 2, 1, 0])));
+
+        // a stripe repeating every 3 pixels can only continue itself east and south
+        let stripes = DMatrix::from_row_slice(2, 6, &[0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        let chunks = chunk_aliases(&stripes, (3, 2), &ChunkSymmetry::none(), true);
+        assert_eq!(chunks.len(), 3);
+        let all_labels: MSu16xNU = chunks.values().collect();
+        let rules = chunk_rules(&all_labels, &chunks, (3, 2), Pruning::Graph);
+        (0..3).for_each(|label| {
+            [4, 6].iter().for_each(|direction| {
+                let set = rules.get(&(*direction, label)).unwrap();
+                assert!(set.is_singleton());
+                assert_eq!(set.imax(), label);
+            })
+        });
     }
 
     #[test]
@@ -323,7 +685,7 @@ mod tests {
         overlaps_n2.insert((2, 2), [0, 1, 0, 0].iter().collect());
         overlaps_n2.insert((5, 1), [0, 0, 1, 0].iter().collect());
 
-        let result_n2 = overlaps(&chunks_n2, 2);
+        let result_n2 = overlaps(&chunks_n2, (2, 2));
         assert_eq!(result_n2, overlaps_n2);
 
         let mut chunks_n3: IndexMap<Chunk, u16> = IndexMap::new();
@@ -334,7 +696,7 @@ mod tests {
         overlaps_n3.insert((0, 0), [0, 1].iter().collect());
         overlaps_n3.insert((23, 1), [1, 0].iter().collect());
 
-        let result_n3 = overlaps(&chunks_n3, 3);
+        let result_n3 = overlaps(&chunks_n3, (3, 3));
 
         assert_eq!(result_n3, overlaps_n3);
 
@@ -350,7 +712,7 @@ mod tests {
         overlaps_n4.insert((8, 0), [1, 0].iter().collect());
         overlaps_n4.insert((39, 0), [1, 0].iter().collect());
 
-        let results_n4 = overlaps(&chunks_n4, 4);
+        let results_n4 = overlaps(&chunks_n4, (4, 4));
 
         assert_eq!(results_n4, overlaps_n4);
     }
@@ -369,7 +731,7 @@ mod tests {
         ]);
 
         let all_labels: MSu16xNU = chunks_n3.values().collect();
-        let raw_graph = create_raw_graph(&all_labels, 3, (2, 2));
+        let raw_graph = create_raw_graph(&all_labels, (3, 3), (2, 2));
 
         assert_eq!(raw_graph.edges.get(&0).unwrap(), edges_n3.get(&0).unwrap());
         assert_eq!(raw_graph.edges.get(&1).unwrap(), edges_n3.get(&1).unwrap());
@@ -3,10 +3,12 @@ use crate::graph::graph::{Graph, Vertices};
 use crate::io::frame_padder::pad_frame;
 use crate::utils::{index_to_coords, is_inside, coords_to_index};
 use crate::io::post_processors::post_processor::PostProcessor;
+use crate::io::olm::olm_parser::ChunkSize;
+use crate::io::utils::Wrap;
 
 use bimap::BiMap;
 use hashbrown::HashMap;
-use image::{imageops, Rgb, RgbImage, Pixel};
+use image::{imageops, Rgba, RgbaImage, Pixel};
 use itertools::Itertools;
 use nalgebra::DMatrix;
 use std::ops::{IndexMut, Index, AddAssign};
@@ -15,22 +17,46 @@ use indexmap::IndexMap;
 use std::ops::Not;
 use crate::MSu16xNU;
 
-const GREEN: Rgb<u8> = Rgb([0, 255, 0]);
+const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
 
 type Chunk = DMatrix<usize>;
-type PixelKeys = BiMap<usize, Rgb<u8>>;
+type PixelKeys = BiMap<usize, Rgba<u8>>;
 
+/// Number of chunks covering `length` pixels, the last chunk is cropped when they don't divide evenly.
+pub fn chunks_to_cover(length: usize, chunk_length: usize) -> usize {
+    match length % chunk_length {
+        0 => length / chunk_length,
+        _ => length / chunk_length + 1
+    }
+}
+
+/// Size in pixels of an output of at least `size` pixels on chunk edges joined by `wrap`.
+/// Wrapped axes are rounded up to whole chunks, as cropping the last chunk would break the seam.
+pub fn output_size((width, height): (usize, usize), (chunk_width, chunk_height): ChunkSize, wrap: Wrap) -> (usize, usize) {
+    let (wrap_x, wrap_y) = wrap.axes();
+    let round = |length, chunk_length, wrapped: bool| match wrapped {
+        true => chunks_to_cover(length, chunk_length) * chunk_length,
+        false => length
+    };
+    (round(width, chunk_width, wrap_x), round(height, chunk_height, wrap_y))
+}
+
+/// Render a graph of chunks covering `width` by `height` pixels, or the `output_size` when the graph edges wrap.
+#[allow(clippy::too_many_arguments)]
 pub fn render(
     filename: &str,
     graph: Graph,
     key: &PixelKeys,
     chunks: &IndexMap<Chunk, u16>,
     (width, height): (usize, usize),
-    chunk_size: usize,
-    opt_post_processors: &Option<Vec<impl PostProcessor<RgbImage>>>
+    chunk_size: ChunkSize,
+    wrap: Wrap,
+    opt_post_processors: &Option<Vec<impl PostProcessor<RgbaImage>>>
 ) {
-    let mut output_image: RgbImage = image::ImageBuffer::new(width as u32, height as u32);
-    let graph_width = width / chunk_size; // in chunks
+    let (width, height) = output_size((width, height), chunk_size, wrap);
+    let mut output_image: RgbaImage = image::ImageBuffer::new(width as u32, height as u32);
+    let (chunk_width, chunk_height) = chunk_size;
+    let graph_width = chunks_to_cover(width, chunk_width); // in chunks
 
     output_image = image::ImageBuffer::new(width as u32, height as u32);
 
@@ -39,16 +65,20 @@ pub fn render(
     for (vertex_index, vertex_chunks) in vertices_as_chunks.into_iter().enumerate() {
         let (vertex_x, vertex_y) = index_to_coords(vertex_index, graph_width);
         // project to pixel coordinates
-        let top_left_pix_x = vertex_x * chunk_size;
-        let top_left_pix_y = vertex_y * chunk_size;
+        let top_left_pix_x = vertex_x * chunk_width;
+        let top_left_pix_y = vertex_y * chunk_height;
 
-        let vertex_as_pixels = chunks_to_pixels(vertex_chunks, key, chunk_size);
+        let vertex_as_pixels = chunks_to_pixels(vertex_chunks, key, chunk_width * chunk_height);
 
+        // chunk pixels are in column major order
         for (pixel_index, pixel) in vertex_as_pixels.into_iter().enumerate() {
-            let (p_y, p_x) = index_to_coords(pixel_index, chunk_size);
+            let (p_y, p_x) = index_to_coords(pixel_index, chunk_height);
             let pixel_y = (top_left_pix_y + p_y) as u32;
             let pixel_x = (top_left_pix_x + p_x) as u32;
-            output_image.put_pixel(pixel_x, pixel_y, pixel);
+            // crop chunks overhanging the right and bottom edges
+            if pixel_x < width as u32 && pixel_y < height as u32 {
+                output_image.put_pixel(pixel_x, pixel_y, pixel);
+            }
         }
     }
 
@@ -67,14 +97,16 @@ pub fn render(
     output_image.save(filename).unwrap();
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn progress(
     filename: &str,
     graphs: Vec<Vertices>,
     key: &PixelKeys,
     chunks: &IndexMap<Chunk, u16>,
     (width, height): (usize, usize),
-    chunk_size: usize,
-    opt_post_processors: &Option<Vec<impl PostProcessor<RgbImage>>>
+    chunk_size: ChunkSize,
+    wrap: Wrap,
+    opt_post_processors: &Option<Vec<impl PostProcessor<RgbaImage>>>
 ) {
     let output_frames = graphs.len();
 
@@ -106,6 +138,7 @@ pub fn progress(
                 chunks,
                 (width, height),
                 chunk_size,
+                wrap,
                 opt_post_processors
             )
         })
@@ -114,7 +147,7 @@ pub fn progress(
 fn vertices_to_chunks(
     vertices: Vertices,
     chunks: &IndexMap<Chunk, u16>,
-    chunk_size: usize,
+    (chunk_width, chunk_height): ChunkSize,
     contradiction_key: usize,
 ) -> Vec<Vec<Chunk>> {
     vertices
@@ -135,18 +168,18 @@ fn vertices_to_chunks(
         })
         .map(|chunks| {
             match chunks.is_empty() {
-                true => vec![DMatrix::from_element(chunk_size, chunk_size, contradiction_key)],
+                true => vec![DMatrix::from_element(chunk_height, chunk_width, contradiction_key)],
                 false => chunks
             }
         })
         .collect()
 }
 
-fn chunks_to_pixels(chunks: Vec<Chunk>, key: &PixelKeys, chunk_size: usize) -> Vec<Rgb<u8>> {
+fn chunks_to_pixels(chunks: Vec<Chunk>, key: &PixelKeys, chunk_pixels: usize) -> Vec<Rgba<u8>> {
     chunks
         // a vec of DMatrix of pixel aliases
         .iter()
-        // map each DMatrix of pixel aliases into an iterator rgb values
+        // map each DMatrix of pixel aliases into an iterator rgba values
         .map(|chunk| {
             chunk
                 .iter()
@@ -157,30 +190,131 @@ fn chunks_to_pixels(chunks: Vec<Chunk>, key: &PixelKeys, chunk_size: usize) -> V
                         .unwrap_or(GREEN)
                 })
         })
-        // sum each matching pixel values for each chunk
+        // sum each matching pixel values for each chunk, with colour channels weighted by alpha
         // using usize to avoid capping on u8 channel size
-        .fold(vec![[0; 3]; chunk_size * chunk_size], |mut acc, chunk| {
+        .fold(vec![[0; 4]; chunk_pixels], |mut acc, chunk| {
             acc
                 .iter_mut()
                 // zip each pixel in acc with each pixel in chunk
                 .zip(chunk)
                 // add each pixel together
                 .for_each(|(acc_pixel, chunk_pixel)| {
-                    acc_pixel[0] += chunk_pixel.channels()[0] as usize;
-                    acc_pixel[1] += chunk_pixel.channels()[1] as usize;
-                    acc_pixel[2] += chunk_pixel.channels()[2] as usize;
+                    let alpha = chunk_pixel.channels()[3] as usize;
+                    acc_pixel[0] += chunk_pixel.channels()[0] as usize * alpha;
+                    acc_pixel[1] += chunk_pixel.channels()[1] as usize * alpha;
+                    acc_pixel[2] += chunk_pixel.channels()[2] as usize * alpha;
+                    acc_pixel[3] += alpha;
                 });
             acc
         })
         .iter()
-        // blend summed pixel values by dividing by length of chunks
-        // unfortunately its way more complex to do this iteratively so accept code duplication
+        // blend summed pixel values, colour by total alpha so transparent chunks don't darken it
+        // and alpha by the length of chunks
         .map(|sum_pixel| {
-            Rgb::from([
-                (sum_pixel[0] / chunks.len()) as u8,
-                (sum_pixel[1] / chunks.len()) as u8,
-                (sum_pixel[2] / chunks.len()) as u8,
-            ])
+            match sum_pixel[3] {
+                0 => Rgba([0, 0, 0, 0]),
+                alpha => Rgba([
+                    (sum_pixel[0] / alpha) as u8,
+                    (sum_pixel[1] / alpha) as u8,
+                    (sum_pixel[2] / alpha) as u8,
+                    (alpha / chunks.len()) as u8,
+                ])
+            }
         })
         .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::post_processors::rescale_image::RescaleImage;
+
+    #[test]
+    fn test_chunks_to_pixels_blends_alpha() {
+        let mut key: PixelKeys = BiMap::new();
+        key.insert(0, Rgba([200, 100, 0, 255]));
+        key.insert(1, Rgba([0, 0, 0, 0]));
+        key.insert(2, Rgba([0, 100, 200, 255]));
+
+        let orange = DMatrix::from_element(1, 1, 0);
+        let clear = DMatrix::from_element(1, 1, 1);
+        let blue = DMatrix::from_element(1, 1, 2);
+
+        let pixels = chunks_to_pixels(vec![orange.clone(), blue], &key, 1);
+        assert_eq!(pixels, vec![Rgba([100, 100, 100, 255])]);
+
+        // a transparent pixel only lowers the alpha, not the colour
+        let pixels = chunks_to_pixels(vec![orange, clear.clone()], &key, 1);
+        assert_eq!(pixels, vec![Rgba([200, 100, 0, 127])]);
+
+        let pixels = chunks_to_pixels(vec![clear.clone(), clear], &key, 1);
+        assert_eq!(pixels, vec![Rgba([0, 0, 0, 0])]);
+    }
+
+    #[test]
+    fn test_render_rectangular_chunks() {
+        let mut key: PixelKeys = BiMap::new();
+        key.insert(0, Rgba([0, 0, 0, 255]));
+        key.insert(1, Rgba([255, 255, 255, 255]));
+
+        // 3 pixels wide and 2 pixels high, white only in the top right corner
+        let mut chunks: IndexMap<Chunk, u16> = IndexMap::new();
+        chunks.insert(DMatrix::from_row_slice(2, 3, &[0, 0, 1, 0, 0, 0]), 1);
+        let mut vertex = MSu16xNU::empty();
+        vertex.insert(0, 1);
+        let graph = Graph::new(vec![vertex; 4], HashMap::new(), vertex);
+
+        let filename = "resources/test/olm_render_rectangular_test.png";
+        render(filename, graph, &key, &chunks, (6, 4), (3, 2), Wrap::None, &None::<Vec<RescaleImage>>);
+        let image = image::open(filename).unwrap().to_rgba8();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(image.dimensions(), (6, 4));
+        let white: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel[0] == 255)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(white, vec![(2, 0), (5, 0), (2, 2), (5, 2)]);
+    }
+
+    #[test]
+    fn test_render_cropped_chunks() {
+        assert_eq!(chunks_to_cover(100, 3), 34);
+        assert_eq!(chunks_to_cover(75, 3), 25);
+
+        let mut key: PixelKeys = BiMap::new();
+        key.insert(0, Rgba([0, 0, 0, 255]));
+        key.insert(1, Rgba([255, 255, 255, 255]));
+
+        // white only in the bottom right corner, which is cropped from the last row and column
+        let mut chunks: IndexMap<Chunk, u16> = IndexMap::new();
+        chunks.insert(DMatrix::from_row_slice(2, 2, &[0, 0, 0, 1]), 1);
+        let mut vertex = MSu16xNU::empty();
+        vertex.insert(0, 1);
+        let graph = Graph::new(vec![vertex; 6], HashMap::new(), vertex);
+
+        let filename = "resources/test/olm_render_cropped_test.png";
+        render(filename, graph.clone(), &key, &chunks, (5, 3), (2, 2), Wrap::None, &None::<Vec<RescaleImage>>);
+        let image = image::open(filename).unwrap().to_rgba8();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(image.dimensions(), (5, 3));
+        let white: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel[0] == 255)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(white, vec![(1, 1), (3, 1)]);
+
+        // a wrapped axis keeps its last chunk whole
+        assert_eq!(output_size((5, 3), (2, 2), Wrap::None), (5, 3));
+        assert_eq!(output_size((5, 3), (2, 2), Wrap::Horizontal), (6, 3));
+        assert_eq!(output_size((5, 3), (2, 2), Wrap::Vertical), (5, 4));
+        assert_eq!(output_size((100, 75), (3, 3), Wrap::Torus), (102, 75));
+        render(filename, graph, &key, &chunks, (5, 3), (2, 2), Wrap::Horizontal, &None::<Vec<RescaleImage>>);
+        let image = image::open(filename).unwrap().to_rgba8();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(image.dimensions(), (6, 3));
+    }
+}
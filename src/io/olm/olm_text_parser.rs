@@ -0,0 +1,230 @@
+use crate::graph::graph::{Graph, Rules};
+use crate::io::olm::olm_parser::{chunk_aliases, chunk_rules, Chunk, ChunkSize, ChunkSymmetry, Pruning};
+use crate::io::olm::olm_renderer::{chunks_to_cover, output_size};
+use crate::io::text_parser::{rows, Tokens, CONTRADICT_TOKEN};
+use crate::io::utils::{invalid_data, Wrap};
+use crate::MSu16xNU;
+use indexmap::IndexMap;
+use nalgebra::DMatrix;
+use std::fs::{read_to_string, write};
+use std::io::Error;
+
+// rules, grapheme keys, all labels and chunks
+pub type TextChunks = (Rules, IndexMap<String, u16>, MSu16xNU, IndexMap<Chunk, u16>);
+
+/// Parse a text sample into the overlapping model, with graphemes aliased in place of pixels.
+/// Output graphs are grids of whole chunks connected in 8 directions.
+pub fn parse(
+    filename: &str,
+    chunk_size: ChunkSize,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
+    pruning: Pruning,
+) -> Result<TextChunks, Error> {
+    let string = read_to_string(filename)?;
+    let rows = rows(&string, Tokens::Graphemes)?;
+    let (chunk_width, chunk_height) = chunk_size;
+    if chunk_width == 0 || chunk_height == 0 || chunk_height > rows.len() || chunk_width > rows[0].len() {
+        return Err(invalid_data(format!("Chunk size {}x{} does not fit in the sample", chunk_width, chunk_height)))
+    }
+
+    let (keys, aliases) = alias_tokens(&rows);
+    let chunks = chunk_aliases(&aliases, chunk_size, symmetry, periodic);
+    if chunks.len() > MSu16xNU::len() {
+        return Err(invalid_data(format!(
+            "Sample has {} unique chunks, more than the {} labels supported", chunks.len(), MSu16xNU::len()
+        )))
+    }
+
+    let all_labels = chunks.values().collect();
+    Ok((chunk_rules(&all_labels, &chunks, chunk_size, pruning), keys, all_labels, chunks))
+}
+
+fn alias_tokens(rows: &[Vec<&str>]) -> (IndexMap<String, u16>, DMatrix<usize>) {
+    let mut keys: IndexMap<String, u16> = IndexMap::new();
+    let aliases: Vec<usize> = rows
+        .concat()
+        .into_iter()
+        .map(|token| {
+            let entry = keys.entry(token.to_string());
+            let alias = entry.index();
+            entry.and_modify(|f| *f += 1).or_insert(1);
+            alias
+        })
+        .collect();
+    (keys, DMatrix::from_row_slice(rows.len(), rows[0].len(), &aliases))
+}
+
+/// Render a graph of chunks as `width` by `height` graphemes, cropping chunks that overhang the edges.
+/// Axes joined by `wrap` are not cropped but rounded up to the `output_size`, to keep the seam.
+/// The graph must be `chunks_to_cover` chunks wide. Cells where the remaining chunks of a vertex disagree, or that have no chunks left, are contradictions.
+pub fn render(
+    filename: &str,
+    graph: Graph,
+    keys: &IndexMap<String, u16>,
+    chunks: &IndexMap<Chunk, u16>,
+    (width, height): (usize, usize),
+    (chunk_width, chunk_height): ChunkSize,
+    wrap: Wrap,
+) {
+    let (width, height) = output_size((width, height), (chunk_width, chunk_height), wrap);
+    let cells: Vec<Vec<&str>> = graph
+        .vertices
+        .iter()
+        .map(|labels| {
+            let candidates: Vec<&Chunk> = labels
+                .into_iter()
+                .enumerate()
+                .filter(|(_, frequency)| frequency > &0)
+                .filter_map(|(label, _)| chunks.get_index(label).map(|(chunk, _)| chunk))
+                .collect();
+            (0..chunk_width * chunk_height)
+                .map(|cell| {
+                    let (y, x) = (cell / chunk_width, cell % chunk_width);
+                    match candidates.split_first() {
+                        Some((first, rest)) if rest.iter().all(|chunk| chunk[(y, x)] == first[(y, x)]) => {
+                            keys.get_index(first[(y, x)]).map_or(CONTRADICT_TOKEN, |(token, _)| token.as_str())
+                        }
+                        _ => CONTRADICT_TOKEN
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let lines: String = cells
+        .chunks_exact(chunks_to_cover(width, chunk_width))
+        .flat_map(|vertices| {
+            (0..chunk_height).map(move |y| {
+                let line: String = vertices
+                    .iter()
+                    .flat_map(|cell| cell[y * chunk_width..(y + 1) * chunk_width].iter().copied())
+                    .take(width)
+                    .collect();
+                line + "\n"
+            })
+        })
+        .take(height)
+        .collect();
+    if write(filename, lines).is_ok() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use crate::io::utils::{make_edges_8_way_grid, make_edges_8_way_grid_wrapped};
+    use crate::wfc::collapse::collapse;
+    use hashbrown::HashMap;
+
+    #[test]
+    fn test_parse_text_chunks() {
+        let symmetry = ChunkSymmetry::default();
+        let (rules, keys, all_labels, chunks) = parse("resources/test/ascii_rooms.txt", (2, 2), &symmetry, false, Pruning::Graph).unwrap();
+
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec!["#", "."]);
+        assert_eq!(keys.values().sum::<u16>(), 48);
+        // every 2x2 chunk of the sample is counted along with its three rotations
+        assert_eq!(chunks.values().sum::<u16>(), 5 * 7 * 4);
+        assert_eq!(all_labels, chunks.values().collect());
+        // a chunk of floor appears inside the rooms so can be placed next to something in every direction
+        let floor = chunks.get_index_of(&DMatrix::from_element(2, 2, 1)).unwrap();
+        (0..8).for_each(|direction| assert!(rules.contains_key(&(direction, floor))));
+    }
+
+    #[test]
+    fn test_parse_text_chunks_errors() {
+        let symmetry = ChunkSymmetry::default();
+        assert_eq!(parse("resources/test/ascii_rooms.txt", (7, 7), &symmetry, false, Pruning::Graph).unwrap_err().kind(), ErrorKind::InvalidData);
+        // more unique chunks of emoji than labels
+        assert_eq!(parse("resources/test/medium_emoji.txt", (3, 3), &symmetry, false, Pruning::Graph).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_render_text_chunks() {
+        let mut keys: IndexMap<String, u16> = IndexMap::new();
+        keys.insert("#".to_string(), 1);
+        keys.insert(".".to_string(), 1);
+        let mut chunks: IndexMap<Chunk, u16> = IndexMap::new();
+        chunks.insert(DMatrix::from_row_slice(2, 2, &[0, 0, 0, 1]), 1);
+        chunks.insert(DMatrix::from_row_slice(2, 2, &[1, 0, 0, 1]), 1);
+
+        // a collapsed chunk, a contradiction and two chunks that only disagree on their top left cell
+        let first: MSu16xNU = [1, 0].iter().collect();
+        let both: MSu16xNU = [1, 1].iter().collect();
+        let graph = Graph::new(vec![first, MSu16xNU::empty(), both], HashMap::new(), both);
+        let filename = "resources/test/olm_text_render_test.txt";
+        render(filename, graph, &keys, &chunks, (6, 2), (2, 2), Wrap::None);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(rendered, "##❌❌❌#\n#.❌❌#.\n");
+
+        // cropped to 5 by 1
+        render(filename, Graph::new(vec![first, first, first], HashMap::new(), both), &keys, &chunks, (5, 1), (2, 2), Wrap::None);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(rendered, "#####\n");
+    }
+
+    #[test]
+    fn test_collapse_text_chunks() {
+        let symmetry = ChunkSymmetry::default();
+        let (rules, keys, all_labels, chunks) = parse("resources/test/ascii_rooms.txt", (2, 2), &symmetry, false, Pruning::Graph).unwrap();
+        let output = Graph::new(vec![all_labels; 12], make_edges_8_way_grid(4, 3), all_labels);
+        let collapsed = collapse(&rules, &output, Some(1), None);
+
+        let filename = "resources/test/olm_text_collapse_test.txt";
+        render(filename, collapsed, &keys, &chunks, (8, 6), (2, 2), Wrap::None);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        let rows: Vec<&str> = rendered.lines().collect();
+        assert_eq!(rows.len(), 6);
+        assert!(rows.iter().all(|row| Tokens::Graphemes.split(row).len() == 8));
+    }
+
+    #[test]
+    fn test_collapse_wrapped_text_chunks() {
+        let symmetry = ChunkSymmetry::none();
+        let (rules, keys, all_labels, chunks) = parse("resources/test/checker_sample.txt", (2, 2), &symmetry, true, Pruning::Graph).unwrap();
+        let (width, height) = (chunks_to_cover(5, 2), chunks_to_cover(3, 2));
+        let output = Graph::new(vec![all_labels; width * height], make_edges_8_way_grid_wrapped(width, height, Wrap::Torus), all_labels);
+        let collapsed = collapse(&rules, &output, Some(1), None);
+
+        // asked for 5 by 3, the wrapped axes keep their last chunk so the output tiles
+        let filename = "resources/test/olm_text_collapse_wrapped_test.txt";
+        render(filename, collapsed, &keys, &chunks, (5, 3), (2, 2), Wrap::Torus);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        let rows: Vec<Vec<&str>> = rendered.lines().map(|row| Tokens::Graphemes.split(row)).collect();
+        assert_eq!((rows[0].len(), rows.len()), (6, 4));
+        // a checkerboard across every seam, including the joined borders
+        (0..4).for_each(|y| (0..6).for_each(|x| {
+            assert_ne!(rows[y][x], CONTRADICT_TOKEN);
+            assert_ne!(rows[y][x], rows[y][(x + 1) % 6]);
+            assert_ne!(rows[y][x], rows[(y + 1) % 4][x]);
+        }));
+    }
+
+    #[test]
+    fn test_collapse_rectangular_text_chunks() {
+        let symmetry = ChunkSymmetry::default();
+        let (rules, keys, all_labels, chunks) = parse("resources/test/ascii_rooms.txt", (3, 2), &symmetry, false, Pruning::Graph).unwrap();
+        assert!(chunks.keys().all(|chunk| chunk.shape() == (2, 3)));
+
+        let output = Graph::new(vec![all_labels; 12], make_edges_8_way_grid(4, 3), all_labels);
+        let collapsed = collapse(&rules, &output, Some(1), None);
+
+        // the last column and row of chunks are cropped
+        let filename = "resources/test/olm_text_collapse_rectangular_test.txt";
+        render(filename, collapsed, &keys, &chunks, (10, 5), (3, 2), Wrap::None);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        let rows: Vec<&str> = rendered.lines().collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|row| Tokens::Graphemes.split(row).len() == 10));
+    }
+}
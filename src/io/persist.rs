@@ -0,0 +1,322 @@
+use crate::graph::graph::{EdgeDirection, Graph, Rules, VertexIndex};
+use crate::io::olm::olm_parser::{Chunk, ChunkSize, ChunkSymmetry, PixelKeys, Pruning};
+use crate::io::utils::invalid_data;
+use crate::MSu16xNU;
+use image::Rgba;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::fs::{read, read_to_string, write};
+use std::io::Error;
+
+// Bump whenever the layout of a saved file, or the rules parsed from the same sample, change.
+// Older files are then rejected on load, and OLM files no longer match their fingerprint.
+pub const FORMAT_VERSION: u32 = 1;
+
+// 64 bit FNV-1a, a fixed algorithm so that fingerprints match across toolchains and machines
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+const OLM_FORMAT: &str = "wfc-rust-olm";
+const TEXT_FORMAT: &str = "wfc-rust-text";
+
+#[derive(Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRule {
+    direction: EdgeDirection,
+    label: usize,
+    labels: Vec<u16>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedChunk {
+    rows: usize,
+    columns: usize,
+    pixels: Vec<usize>, // row major
+    frequency: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedOlm {
+    format: String,
+    version: u32,
+    fingerprint: u64,
+    rules: Vec<SavedRule>,
+    pixel_keys: Vec<(usize, [u8; 4])>,
+    all_labels: Vec<u16>,
+    chunks: Vec<SavedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedText {
+    format: String,
+    version: u32,
+    vertices: Vec<Vec<u16>>,
+    edges: Vec<(VertexIndex, Vec<(VertexIndex, EdgeDirection)>)>,
+    all_labels: Vec<u16>,
+    keys: Vec<(String, u16)>,
+}
+
+/// Fingerprint of the samples and options given to `olm_parser::parse_multiple`.
+/// A saved file only loads with the fingerprint it was saved with, so edited samples are parsed again.
+pub fn olm_fingerprint(
+    filenames: &[&str],
+    chunk_size: ChunkSize,
+    symmetry: &ChunkSymmetry,
+    periodic: bool,
+    pruning: Pruning,
+) -> Result<u64, Error> {
+    let mut hash = fnv1a(FNV_OFFSET, &FORMAT_VERSION.to_le_bytes());
+    for filename in filenames {
+        let sample = read(filename)?;
+        hash = fnv1a(hash, &(sample.len() as u64).to_le_bytes());
+        hash = fnv1a(hash, &sample);
+    }
+    hash = fnv1a(hash, &(chunk_size.0 as u64).to_le_bytes());
+    hash = fnv1a(hash, &(chunk_size.1 as u64).to_le_bytes());
+    let flags: Vec<u8> = [
+        symmetry.rotate,
+        symmetry.reflect_vertical,
+        symmetry.reflect_horizontal,
+        symmetry.reflect_diagonal,
+        periodic,
+        pruning == Pruning::Direct,
+    ]
+        .iter()
+        .map(|flag| *flag as u8)
+        .collect();
+    Ok(fnv1a(hash, &flags))
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Save the output of `olm_parser::parse` so it can be reused without parsing the sample again.
+pub fn save_olm(
+    filename: &str,
+    fingerprint: u64,
+    rules: &Rules,
+    pixel_keys: &PixelKeys,
+    all_labels: &MSu16xNU,
+    chunks: &IndexMap<Chunk, u16>,
+) -> Result<(), Error> {
+    let mut pixel_keys: Vec<(usize, [u8; 4])> = pixel_keys
+        .iter()
+        .map(|(alias, pixel)| (*alias, pixel.0))
+        .collect();
+    pixel_keys.sort_unstable();
+
+    let saved = SavedOlm {
+        format: OLM_FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        fingerprint,
+        rules: save_rules(rules),
+        pixel_keys,
+        all_labels: save_labels(all_labels),
+        chunks: chunks
+            .iter()
+            .map(|(chunk, frequency)| SavedChunk {
+                rows: chunk.nrows(),
+                columns: chunk.ncols(),
+                pixels: chunk.transpose().iter().copied().collect(),
+                frequency: *frequency,
+            })
+            .collect(),
+    };
+    write_json(filename, &saved)
+}
+
+pub fn load_olm(
+    filename: &str,
+    fingerprint: u64,
+) -> Result<(Rules, PixelKeys, MSu16xNU, IndexMap<Chunk, u16>), Error> {
+    let saved: SavedOlm = read_json(filename, OLM_FORMAT)?;
+    if saved.fingerprint != fingerprint {
+        return Err(invalid_data(format!("{} was saved from other samples or parse options", filename)))
+    }
+
+    let pixel_keys = saved.pixel_keys
+        .into_iter()
+        .map(|(alias, pixel)| (alias, Rgba(pixel)))
+        .collect();
+    let chunks = saved.chunks
+        .into_iter()
+        .map(|chunk| {
+            if chunk.rows * chunk.columns != chunk.pixels.len() {
+                return Err(invalid_data("Chunk dimensions do not match its pixels".to_string()))
+            }
+            Ok((Chunk::from_row_slice(chunk.rows, chunk.columns, &chunk.pixels), chunk.frequency))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok((load_rules(saved.rules)?, pixel_keys, load_labels(&saved.all_labels)?, chunks))
+}
+
+/// Save the output of `text_parser::parse` so it can be reused without parsing the sample again.
+pub fn save_text(filename: &str, graph: &Graph, keys: &IndexMap<String, u16>) -> Result<(), Error> {
+    let mut edges: Vec<(VertexIndex, Vec<(VertexIndex, EdgeDirection)>)> = graph.edges
+        .iter()
+        .map(|(index, neighbours)| (*index, neighbours.clone()))
+        .collect();
+    edges.sort_unstable_by_key(|(index, _)| *index);
+
+    let saved = SavedText {
+        format: TEXT_FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        vertices: graph.vertices.iter().map(save_labels).collect(),
+        edges,
+        all_labels: save_labels(&graph.all_labels),
+        keys: keys.iter().map(|(key, frequency)| (key.clone(), *frequency)).collect(),
+    };
+    write_json(filename, &saved)
+}
+
+pub fn load_text(filename: &str) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let saved: SavedText = read_json(filename, TEXT_FORMAT)?;
+    let vertices = saved.vertices
+        .iter()
+        .map(|labels| load_labels(labels))
+        .collect::<Result<_, Error>>()?;
+    let graph = Graph::new(vertices, saved.edges.into_iter().collect(), load_labels(&saved.all_labels)?);
+    Ok((graph, saved.keys.into_iter().collect()))
+}
+
+fn write_json<T: Serialize>(filename: &str, value: &T) -> Result<(), Error> {
+    let json = serde_json::to_string(value).map_err(|e| invalid_data(e.to_string()))?;
+    write(filename, json)
+}
+
+// Check the format and version before reading the rest of the file.
+fn read_json<T: DeserializeOwned>(filename: &str, format: &str) -> Result<T, Error> {
+    let string = read_to_string(filename)?;
+    let header: Header = serde_json::from_str(&string).map_err(|e| invalid_data(e.to_string()))?;
+    if header.format != format {
+        return Err(invalid_data(format!("Expected a {} file, found {}", format, header.format)))
+    }
+    if header.version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported file version {}, expected version {}", header.version, FORMAT_VERSION
+        )))
+    }
+    serde_json::from_str(&string).map_err(|e| invalid_data(e.to_string()))
+}
+
+fn save_labels(labels: &MSu16xNU) -> Vec<u16> {
+    labels.into_iter().collect()
+}
+
+fn load_labels(labels: &[u16]) -> Result<MSu16xNU, Error> {
+    if labels.len() > MSu16xNU::len() {
+        return Err(invalid_data(format!(
+            "File has {} labels, more than the {} labels supported", labels.len(), MSu16xNU::len()
+        )))
+    }
+    Ok(labels.iter().collect())
+}
+
+fn save_rules(rules: &Rules) -> Vec<SavedRule> {
+    let mut saved: Vec<SavedRule> = rules
+        .iter()
+        .map(|((direction, label), labels)| SavedRule {
+            direction: *direction,
+            label: *label,
+            labels: save_labels(labels),
+        })
+        .collect();
+    saved.sort_unstable_by_key(|rule| (rule.direction, rule.label));
+    saved
+}
+
+fn load_rules(saved: Vec<SavedRule>) -> Result<Rules, Error> {
+    saved
+        .into_iter()
+        .map(|rule| Ok(((rule.direction, rule.label), load_labels(&rule.labels)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use crate::io::{olm::olm_parser, text_parser};
+    use std::fs::remove_file;
+
+    #[test]
+    fn test_olm_round_trip() {
+        let sample = "resources/test/chunk_image_test.png";
+        let symmetry = ChunkSymmetry::default();
+        let (rules, keys, all_labels, chunks) = olm_parser::parse(sample, (2, 2), &symmetry, false, Pruning::Graph);
+        let fingerprint = olm_fingerprint(&[sample], (2, 2), &symmetry, false, Pruning::Graph).unwrap();
+
+        let filename = "resources/test/persist_olm_test.json";
+        save_olm(filename, fingerprint, &rules, &keys, &all_labels, &chunks).unwrap();
+        let loaded = load_olm(filename, fingerprint);
+        let other_options = load_olm(filename, fingerprint + 1);
+        remove_file(filename).unwrap();
+
+        assert_eq!(other_options.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let (loaded_rules, loaded_keys, loaded_labels, loaded_chunks) = loaded.unwrap();
+        assert_eq!(loaded_rules, rules);
+        assert_eq!(loaded_keys, keys);
+        assert_eq!(loaded_labels, all_labels);
+        assert_eq!(loaded_chunks, chunks);
+    }
+
+    #[test]
+    fn test_olm_fingerprint() {
+        let sample = "resources/test/chunk_image_test.png";
+        let symmetry = ChunkSymmetry::default();
+        let fingerprint = olm_fingerprint(&[sample], (2, 2), &symmetry, false, Pruning::Graph).unwrap();
+
+        assert_eq!(olm_fingerprint(&[sample], (2, 2), &symmetry, false, Pruning::Graph).unwrap(), fingerprint);
+        assert_ne!(olm_fingerprint(&["resources/test/3Bricks.png"], (2, 2), &symmetry, false, Pruning::Graph).unwrap(), fingerprint);
+        assert_ne!(olm_fingerprint(&[sample], (3, 2), &symmetry, false, Pruning::Graph).unwrap(), fingerprint);
+        assert_ne!(olm_fingerprint(&[sample], (2, 2), &ChunkSymmetry::all(), false, Pruning::Graph).unwrap(), fingerprint);
+        assert_ne!(olm_fingerprint(&[sample], (2, 2), &symmetry, true, Pruning::Graph).unwrap(), fingerprint);
+        assert_ne!(olm_fingerprint(&[sample], (2, 2), &symmetry, false, Pruning::Direct).unwrap(), fingerprint);
+        assert!(olm_fingerprint(&["resources/test/missing.png"], (2, 2), &symmetry, false, Pruning::Graph).is_err());
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        // published FNV-1a test vectors
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET, b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let (graph, keys) = text_parser::parse("resources/test/medium_emoji.txt", true).unwrap();
+
+        let filename = "resources/test/persist_text_test.json";
+        save_text(filename, &graph, &keys).unwrap();
+        let loaded = load_text(filename);
+        remove_file(filename).unwrap();
+
+        let (loaded_graph, loaded_keys) = loaded.unwrap();
+        assert_eq!(loaded_graph.vertices, graph.vertices);
+        assert_eq!(loaded_graph.edges, graph.edges);
+        assert_eq!(loaded_graph.all_labels, graph.all_labels);
+        assert_eq!(loaded_keys, keys);
+    }
+
+    #[test]
+    fn test_load_rejects_other_versions_and_formats() {
+        let filename = "resources/test/persist_version_test.json";
+        write(filename, r#"{"format": "wfc-rust-text", "version": 0}"#).unwrap();
+        let old_version = load_text(filename);
+        let wrong_format = load_olm(filename, 0);
+        remove_file(filename).unwrap();
+
+        assert_eq!(old_version.unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(wrong_format.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,4 @@
-use image::RgbImage;
+use image::{ImageBuffer, Pixel};
 use crate::io::post_processors::post_processor::PostProcessor;
 use crate::utils::index_to_coords;
 use itertools::Itertools;
@@ -11,12 +11,11 @@ impl RescaleImage {
     pub fn new(scale: usize) -> RescaleImage { RescaleImage { scale } }
 }
 
-impl PostProcessor<RgbImage> for RescaleImage {
-    fn process(&self, input: &RgbImage) -> RgbImage {
+impl<P: Pixel + 'static> PostProcessor<ImageBuffer<P, Vec<P::Subpixel>>> for RescaleImage {
+    fn process(&self, input: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
         let scale = self.scale;
         let (width, height) = input.dimensions();
-        let mut scaled_img: RgbImage = image::ImageBuffer::new(width * scale as u32,
-                                                               height * scale as u32);
+        let mut scaled_img = ImageBuffer::new(width * scale as u32, height * scale as u32);
         input
             .pixels()
             .enumerate()
@@ -1,18 +1,18 @@
-use image::{Rgb, RgbImage};
+use image::{ImageBuffer, Pixel};
 use crate::io::post_processors::post_processor::PostProcessor;
 
 // extremely simple image post processor for demonstration purposes
-pub struct UnitImage {
-    color: Rgb<u8>
+pub struct UnitImage<P: Pixel> {
+    color: P
 }
 
-impl UnitImage {
-    pub fn new(color: Rgb<u8>) -> UnitImage { UnitImage { color } }
+impl<P: Pixel> UnitImage<P> {
+    pub fn new(color: P) -> UnitImage<P> { UnitImage { color } }
 }
 
-impl PostProcessor<RgbImage> for UnitImage {
-    fn process(&self, input: &RgbImage) -> RgbImage {
+impl<P: Pixel + 'static> PostProcessor<ImageBuffer<P, Vec<P::Subpixel>>> for UnitImage<P> {
+    fn process(&self, input: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
         let (width, height) = input.dimensions();
-        RgbImage::from_pixel(width, height, self.color)
+        ImageBuffer::from_pixel(width, height, self.color)
     }
 }
@@ -0,0 +1,246 @@
+use crate::graph::graph::{EdgeDirection, Rules, insert_rule};
+use crate::io::utils::invalid_data;
+use crate::MSu16xNU;
+use hashbrown::HashMap;
+use image::Rgb;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::io::Error;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Direction names in the order of the grid direction ids used by `make_edges_8_way_grid`
+// and `make_edges_cardinal_grid`. The opposite of direction d is 7 - d.
+const DIRECTION_NAMES: [&str; 8] = [
+    "north_west", "north", "north_east", "west", "east", "south_west", "south", "south_east",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelKey {
+    pub weight: u16,
+    pub glyph: Option<String>,
+    pub colour: Option<Rgb<u8>>,
+}
+
+// label name -> key, the index of a name is its label
+pub type LabelKeys = IndexMap<String, LabelKey>;
+
+#[derive(Deserialize)]
+struct LabelEntry {
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: u16,
+    glyph: Option<String>,
+    colour: Option<[u8; 3]>,
+}
+
+fn default_weight() -> u16 { 1 }
+
+#[derive(Deserialize)]
+struct AdjacencyEntry {
+    label: String,
+    // direction name -> labels allowed in that direction
+    #[serde(flatten)]
+    directions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SymmetricEntry {
+    pair: (String, String),
+    directions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    labels: Vec<LabelEntry>,
+    #[serde(default)]
+    adjacency: Vec<AdjacencyEntry>,
+    #[serde(default)]
+    symmetric: Vec<SymmetricEntry>,
+}
+
+/*
+Load a hand written TOML rule file for grid tile models:
+
+[[labels]]
+name = "road"
+weight = 2          # optional, defaults to 1, must be positive
+glyph = "="         # optional
+colour = [90, 90, 90] # optional
+
+[[adjacency]]       # one way: road may have field to its south
+label = "road"
+south = ["field"]
+
+[[symmetric]]       # road may have road to its east, and so road may have road to its west
+pair = ["road", "road"]
+directions = ["east"]
+
+Label frequencies in the returned rules are the weights of the neighbouring labels.
+*/
+pub fn parse(filename: &str) -> Result<(Rules, MSu16xNU, LabelKeys), Error> {
+    read_to_string(filename).and_then(|string| parse_str(&string))
+}
+
+pub fn parse_str(string: &str) -> Result<(Rules, MSu16xNU, LabelKeys), Error> {
+    let file: RuleFile = toml::from_str(string).map_err(|e| invalid_data(e.to_string()))?;
+
+    if file.labels.len() > MSu16xNU::len() {
+        return Err(invalid_data(format!(
+            "Rule file declares {} labels, more than the {} labels supported", file.labels.len(), MSu16xNU::len()
+        )))
+    }
+
+    let mut keys = LabelKeys::new();
+    for entry in file.labels {
+        if keys.contains_key(&entry.name) {
+            return Err(invalid_data(format!("Label {} is declared more than once", entry.name)))
+        }
+        if entry.weight == 0 {
+            return Err(invalid_data(format!("Label {} has weight 0, it could never be placed", entry.name)))
+        }
+        let key = LabelKey { weight: entry.weight, glyph: entry.glyph, colour: entry.colour.map(Rgb) };
+        keys.insert(entry.name, key);
+    }
+
+    let mut allowed: Vec<(EdgeDirection, usize, usize)> = Vec::new();
+    for entry in &file.adjacency {
+        let from = label(&keys, &entry.label)?;
+        for (direction_name, neighbours) in &entry.directions {
+            let direction = direction(direction_name)?;
+            for neighbour in neighbours {
+                allowed.push((direction, from, label(&keys, neighbour)?));
+            }
+        }
+    }
+    for entry in &file.symmetric {
+        let (first, second) = (label(&keys, &entry.pair.0)?, label(&keys, &entry.pair.1)?);
+        for direction_name in &entry.directions {
+            let direction = direction(direction_name)?;
+            allowed.push((direction, first, second));
+            allowed.push((7 - direction, second, first));
+        }
+    }
+
+    let rules = allowed
+        .into_iter()
+        .fold(HashMap::new(), |mut rules, (direction, from, to)| {
+            insert_rule(&mut rules, direction, from, to, keys[to].weight);
+            rules
+        });
+
+    let all_labels = keys.values().map(|key| &key.weight).collect();
+
+    Ok((rules, all_labels, keys))
+}
+
+/// Keys for `text_parser::render`, labels without a glyph use the first grapheme of their name.
+pub fn glyph_keys(keys: &LabelKeys) -> IndexMap<String, u16> {
+    keys
+        .iter()
+        .map(|(name, key)| {
+            let glyph = key.glyph.as_deref().or_else(|| name.graphemes(true).next()).unwrap_or("?");
+            (glyph.to_string(), key.weight)
+        })
+        .collect()
+}
+
+fn label(keys: &LabelKeys, name: &str) -> Result<usize, Error> {
+    keys.get_index_of(name).ok_or_else(|| invalid_data(format!("Unknown label: {}", name)))
+}
+
+fn direction(name: &str) -> Result<EdgeDirection, Error> {
+    DIRECTION_NAMES
+        .iter()
+        .position(|direction| direction == &name)
+        .map(|direction| direction as EdgeDirection)
+        .ok_or_else(|| invalid_data(format!("Unknown direction: {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use crate::graph::graph::Graph;
+    use crate::io::utils::make_edges_cardinal_grid;
+    use crate::wfc::collapse::collapse;
+
+    #[test]
+    fn test_parse_rule_file() {
+        let (rules, all_labels, keys) = parse("resources/test/road_rules.toml").unwrap();
+
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec!["road", "field", "house"]);
+        assert_eq!(keys["road"].glyph.as_deref(), Some("="));
+        assert_eq!(keys["house"].colour, Some(Rgb([200, 80, 40])));
+        assert_eq!(all_labels, [2, 5, 1].iter().collect());
+
+        //                          road, field, house
+        let road_east: MSu16xNU = [2, 0, 0].iter().collect();
+        let road_north: MSu16xNU = [0, 5, 1].iter().collect();
+        let house_north: MSu16xNU = [0, 5, 0].iter().collect();
+        // east of road is road, and so is west by symmetry
+        assert_eq!(rules.get(&(4, 0)).unwrap(), &road_east);
+        assert_eq!(rules.get(&(3, 0)).unwrap(), &road_east);
+        assert_eq!(rules.get(&(1, 0)).unwrap(), &road_north);
+        // field may have house to its south, so by symmetry house may have field to its north
+        assert_eq!(rules.get(&(1, 2)).unwrap(), &house_north);
+        assert!(rules.get(&(6, 2)).unwrap().is_subset(&[2, 5, 0].iter().collect()));
+    }
+
+    #[test]
+    fn test_rule_file_defaults() {
+        let rules_file = r##"
+            [[labels]]
+            name = "a"
+
+            [[labels]]
+            name = "b"
+            glyph = "#"
+
+            [[symmetric]]
+            pair = ["a", "b"]
+            directions = ["north_east"]
+        "##;
+        let (rules, all_labels, keys) = parse_str(rules_file).unwrap();
+        assert_eq!(all_labels, [1, 1].iter().collect());
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules.get(&(2, 0)).unwrap(), &[0, 1].iter().collect());
+        assert_eq!(rules.get(&(5, 1)).unwrap(), &[1, 0].iter().collect());
+
+        let glyphs = glyph_keys(&keys);
+        assert_eq!(glyphs.keys().collect::<Vec<_>>(), vec!["a", "#"]);
+    }
+
+    #[test]
+    fn test_rule_file_errors() {
+        let unknown_label = "[[labels]]\nname = \"a\"\n[[adjacency]]\nlabel = \"b\"\n";
+        let unknown_direction = "[[labels]]\nname = \"a\"\n[[adjacency]]\nlabel = \"a\"\nup = [\"a\"]\n";
+        let duplicate = "[[labels]]\nname = \"a\"\n[[labels]]\nname = \"a\"\n";
+        let missing_labels = "[[adjacency]]\nlabel = \"a\"\n";
+        let zero_weight = "[[labels]]\nname = \"a\"\nweight = 0\n";
+
+        for rules_file in [unknown_label, unknown_direction, duplicate, missing_labels, zero_weight].iter() {
+            assert_eq!(parse_str(rules_file).unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_collapse_with_rule_file() {
+        let (rules, all_labels, _) = parse("resources/test/road_rules.toml").unwrap();
+        let edges = make_edges_cardinal_grid(6, 6);
+        let graph = Graph::new(vec![all_labels; 36], edges, all_labels);
+        let result = collapse(&rules, &graph, Some(3), None);
+
+        assert!(result.vertices.iter().all(|labels| labels.is_singleton()));
+        // every adjacency in the output was declared in the rule file
+        result.edges.iter().for_each(|(from, neighbours)| {
+            let from_labels = result.vertices[*from as usize];
+            neighbours.iter().for_each(|(to, direction)| {
+                let to_labels = result.vertices[*to as usize];
+                let allowed = rules.get(&(*direction, from_labels.imax())).unwrap();
+                assert!(!to_labels.intersection(allowed).is_empty());
+            })
+        });
+    }
+}
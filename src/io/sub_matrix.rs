@@ -33,13 +33,11 @@ impl<T: Scalar> SubMatrix for DMatrix<T>
         self.remove_rows(offset, rows - offset)
     }
 
+    // copies the sub matrix in one go rather than cropping a clone of the whole matrix
     fn sub_matrix(&self, position: (usize, usize), size: (usize, usize)) -> DMatrix<T> {
         self
-            .clone()
-            .crop_left(position.0)
-            .crop_right(size.0)
-            .crop_top(position.1)
-            .crop_bottom(size.1)
+            .slice((position.1, position.0), (size.1, size.0))
+            .into_owned()
     }
 }
 
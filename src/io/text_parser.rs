@@ -1,18 +1,282 @@
-use crate::graph::graph::{Edges, Graph, Vertices};
-use crate::io::utils::{make_edges_cardinal_grid, make_edges_8_way_grid};
+use crate::graph::graph::{Edges, Graph, Rules, Vertices, merge_keys, merge_rules, remap_labels, remap_rules, saturating_sum};
+use crate::io::frame_padder::pad_frame;
+use crate::io::utils::{invalid_data, make_edges_cardinal_grid, make_edges_8_way_grid, Stencil};
 use crate::MSu16xNU;
+use hashbrown::HashMap;
+use std::borrow::Cow;
 use std::fs::{read_to_string, write};
 use std::io::Error;
 use std::iter;
 use indexmap::IndexMap;
+use unicode_segmentation::UnicodeSegmentation;
 
-pub fn parse(filename: &str, intercardinals: bool) -> Result<(Graph, IndexMap<char, u16>), Error> {
-    read_to_string(filename).map(|string| {
-        let edges = make_edges(&string, intercardinals);
-        let (char_frequency, vertices) = char_maps(&string);
-        let all_labels = char_frequency.values().collect();
-        (Graph::new(vertices, edges, all_labels), char_frequency)
+/// How the lines of a text sample are split into labels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokens {
+    /// Every extended grapheme cluster is a label, so emoji sequences and accented letters stay whole.
+    Graphemes,
+    /// Labels are separated by whitespace and may be several characters long.
+    Words,
+}
+
+impl Tokens {
+    pub fn split(self, line: &str) -> Vec<&str> {
+        match self {
+            Tokens::Graphemes => line.graphemes(true).collect(),
+            Tokens::Words => line.split_whitespace().collect(),
+        }
+    }
+
+    fn separator(self) -> &'static str {
+        match self {
+            Tokens::Graphemes => "",
+            Tokens::Words => " ",
+        }
+    }
+}
+
+pub fn parse(filename: &str, intercardinals: bool) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    parse_tokens(filename, intercardinals, Tokens::Graphemes)
+}
+
+pub fn parse_tokens(
+    filename: &str,
+    intercardinals: bool,
+    tokens: Tokens,
+) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let string = read_to_string(filename)?;
+    let rows = rows(&string, tokens)?;
+    let edges = make_edges(&rows, intercardinals);
+    make_graph(&rows, edges)
+}
+
+/// Parse several text samples and combine their rules.
+/// Labels are remapped through a key table shared by all samples, so the returned rules, set of all
+/// labels and keys line up with each other regardless of the graphemes each sample uses.
+pub fn parse_multiple(
+    filenames: &[&str],
+    intercardinals: bool,
+) -> Result<(Rules, MSu16xNU, IndexMap<String, u16>), Error> {
+    merge_samples(filenames.iter().map(|filename| parse(filename, intercardinals)))
+}
+
+/// Rotated and mirrored copies of a text sample to add to its rules.
+#[derive(Debug, Clone, Default)]
+pub struct Augment {
+    /// Add the sample rotated by 90, 180 and 270 degrees clockwise.
+    pub rotate: bool,
+    /// Add the sample, and its rotations if any, mirrored left to right.
+    pub mirror: bool,
+    /// Glyph -> the glyph rotated 90 degrees clockwise, glyphs missing from the table are unchanged.
+    pub rotations: HashMap<String, String>,
+    /// Glyph -> the glyph mirrored left to right, glyphs missing from the table are unchanged.
+    pub mirrors: HashMap<String, String>,
+}
+
+impl Augment {
+    /// Rotate and mirror with tables for box drawing lines and arrows.
+    pub fn box_drawing() -> Augment {
+        let table = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+        };
+        Augment {
+            rotate: true,
+            mirror: true,
+            rotations: table(&[
+                ("─", "│"), ("│", "─"), ("┌", "┐"), ("┐", "┘"), ("┘", "└"), ("└", "┌"),
+                ("├", "┬"), ("┬", "┤"), ("┤", "┴"), ("┴", "├"),
+                ("→", "↓"), ("↓", "←"), ("←", "↑"), ("↑", "→"),
+            ]),
+            mirrors: table(&[
+                ("┌", "┐"), ("┐", "┌"), ("└", "┘"), ("┘", "└"), ("├", "┤"), ("┤", "├"),
+                ("→", "←"), ("←", "→"),
+            ]),
+        }
+    }
+}
+
+/// Parse a text sample along with the copies of it described by `augment`, and combine their rules
+/// as `parse_multiple` does.
+pub fn parse_augmented(
+    filename: &str,
+    intercardinals: bool,
+    augment: &Augment,
+) -> Result<(Rules, MSu16xNU, IndexMap<String, u16>), Error> {
+    let string = read_to_string(filename)?;
+    let sample = rows(&string, Tokens::Graphemes)?;
+
+    let rotations = if augment.rotate { 4 } else { 1 };
+    let rotated: Vec<Vec<Vec<&str>>> = iter::successors(Some(sample), |rows| {
+        Some(rotate_rows(rows, &augment.rotations))
     })
+        .take(rotations)
+        .collect();
+    let mirrored: Vec<Vec<Vec<&str>>> = if augment.mirror {
+        rotated.iter().map(|rows| mirror_rows(rows, &augment.mirrors)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let copies = rotated.iter().chain(mirrored.iter());
+    merge_samples(copies.map(|rows| make_graph(rows, make_edges(rows, intercardinals))))
+}
+
+fn merge_samples(
+    samples: impl IntoIterator<Item = Result<(Graph, IndexMap<String, u16>), Error>>,
+) -> Result<(Rules, MSu16xNU, IndexMap<String, u16>), Error> {
+    let mut shared_keys: IndexMap<String, u16> = IndexMap::new();
+    let mut all_labels = MSu16xNU::empty();
+    let mut sample_rules = Vec::new();
+
+    for sample in samples {
+        let (graph, keys) = sample?;
+        let mapping = merge_keys(&mut shared_keys, &keys);
+        if shared_keys.len() > MSu16xNU::len() {
+            return Err(invalid_data(format!(
+                "Samples use {} labels, more than the {} labels supported", shared_keys.len(), MSu16xNU::len()
+            )))
+        }
+        all_labels = saturating_sum(&all_labels, &remap_labels(&graph.all_labels, &mapping));
+        sample_rules.push(remap_rules(&graph.rules(), &mapping));
+    }
+
+    Ok((merge_rules(sample_rules), all_labels, shared_keys))
+}
+
+fn transform_glyph<'a>(glyph: &'a str, table: &'a HashMap<String, String>) -> &'a str {
+    table.get(glyph).map_or(glyph, String::as_str)
+}
+
+// the rows rotated 90 degrees clockwise
+fn rotate_rows<'a>(rows: &[Vec<&'a str>], table: &'a HashMap<String, String>) -> Vec<Vec<&'a str>> {
+    let (width, height) = dimensions(rows);
+    (0..width)
+        .map(|x| (0..height).rev().map(|y| transform_glyph(rows[y][x], table)).collect())
+        .collect()
+}
+
+// the rows mirrored left to right
+fn mirror_rows<'a>(rows: &[Vec<&'a str>], table: &'a HashMap<String, String>) -> Vec<Vec<&'a str>> {
+    rows
+        .iter()
+        .map(|row| row.iter().rev().map(|glyph| transform_glyph(glyph, table)).collect())
+        .collect()
+}
+
+/// Parse a text sample, connecting graphemes with the offsets of `stencil`.
+/// The output graph must be built with the same stencil for the rules to apply.
+pub fn parse_stencil(filename: &str, stencil: &Stencil) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let string = read_to_string(filename)?;
+    let rows = rows(&string, Tokens::Graphemes)?;
+    let (width, height) = dimensions(&rows);
+    make_graph(&rows, stencil.make_edges(width, height))
+}
+
+fn make_graph(rows: &[Vec<&str>], edges: Edges) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let (token_frequency, vertices) = token_maps(rows.concat())?;
+    let all_labels = token_frequency.values().collect();
+    Ok((Graph::new(vertices, edges, all_labels), token_frequency))
+}
+
+// Split a sample into rows of tokens, accepting \n and \r\n line endings and trailing empty lines.
+pub(crate) fn rows(string: &str, tokens: Tokens) -> Result<Vec<Vec<&str>>, Error> {
+    split_rows(string, |line| Ok(tokens.split(line)))
+}
+
+fn split_rows<'a>(
+    string: &'a str,
+    split: impl Fn(&'a str) -> Result<Vec<&'a str>, Error>,
+) -> Result<Vec<Vec<&'a str>>, Error> {
+    if !string.contains('\n') {
+        return Err(invalid_data("Text sample has no newline, every line must end with one".to_string()))
+    }
+    let rows: Vec<Vec<&str>> = string
+        .trim_end_matches(&['\n', '\r'][..])
+        .lines()
+        .map(split)
+        .collect::<Result<_, Error>>()?;
+    let width = rows.first().map_or(0, Vec::len);
+    if width == 0 {
+        return Err(invalid_data("Text sample is empty".to_string()))
+    }
+    match rows.iter().position(|row| row.len() != width) {
+        Some(line) => Err(invalid_data(format!(
+            "Line {} is {} tokens wide but the first line is {} tokens wide", line + 1, rows[line].len(), width
+        ))),
+        None => Ok(rows)
+    }
+}
+
+pub const WILDCARD: &str = "?";
+
+/*
+Build an output graph from a text template, with the keys of a parsed sample:
+
+┌─?──
+│.[.│]??
+?????
+
+Each grapheme pins its vertex to that label, ? allows any label and a bracketed set such as [.│]
+allows only the labels inside it. Returns the graph and its width for rendering.
+*/
+pub fn parse_template(
+    filename: &str,
+    key: &IndexMap<String, u16>,
+    intercardinals: bool,
+) -> Result<(Graph, usize), Error> {
+    let string = read_to_string(filename)?;
+    let rows = split_rows(&string, template_cells)?;
+    let all_labels: MSu16xNU = key.values().collect();
+    let vertices = rows
+        .concat()
+        .into_iter()
+        .map(|cell| template_labels(cell, key, &all_labels))
+        .collect::<Result<_, Error>>()?;
+    let (width, _) = dimensions(&rows);
+    Ok((Graph::new(vertices, make_edges(&rows, intercardinals), all_labels), width))
+}
+
+// graphemes of a template line, with each bracketed set kept whole
+fn template_cells(line: &str) -> Result<Vec<&str>, Error> {
+    let mut cells = Vec::new();
+    let mut set_start = None;
+    for (index, grapheme) in line.grapheme_indices(true) {
+        match (set_start, grapheme) {
+            (None, "[") => set_start = Some(index),
+            (Some(start), "]") => {
+                cells.push(&line[start..index + 1]);
+                set_start = None
+            }
+            (Some(_), _) => {}
+            (None, _) => cells.push(grapheme),
+        }
+    }
+    match set_start {
+        Some(_) => Err(invalid_data(format!("Unclosed [ in template line: {}", line))),
+        None => Ok(cells)
+    }
+}
+
+fn template_labels(cell: &str, key: &IndexMap<String, u16>, all_labels: &MSu16xNU) -> Result<MSu16xNU, Error> {
+    let graphemes: Vec<&str> = match cell {
+        WILDCARD => return Ok(*all_labels),
+        set if set.starts_with('[') && set.ends_with(']') && set.len() > 1 => {
+            set[1..set.len() - 1].graphemes(true).collect()
+        }
+        grapheme => vec![grapheme],
+    };
+    if graphemes.is_empty() {
+        return Err(invalid_data("Empty [] in template".to_string()))
+    }
+    graphemes
+        .into_iter()
+        .try_fold(MSu16xNU::empty(), |mut labels, grapheme| {
+            let (label, _, frequency) = key
+                .get_full(grapheme)
+                .ok_or_else(|| invalid_data(format!("Template uses {} which is not in the sample", grapheme)))?;
+            labels.insert(label, *frequency);
+            Ok(labels)
+        })
 }
 
 // 3 syllabale rhyming couplet poem to remind us why we should use sorted data structures
@@ -20,25 +284,33 @@ pub fn parse(filename: &str, intercardinals: bool) -> Result<(Graph, IndexMap<ch
 // Tori sea
 // Hashmap lame
 // Vecs have game
-fn char_maps(string: &str) -> (IndexMap<char, u16>, Vec<MSu16xNU>) {
-    let mut char_frequencies: IndexMap<char, u16> = IndexMap::new();
-    let vertices: Vec<MSu16xNU> = string
-        .chars()
-        .filter(|char| char != &'\n')
-        .map(|char| {
-            let label = char_frequencies.entry(char).index();
-            char_frequencies.entry(char).and_modify(|f| *f += 1).or_insert(1);
+pub(crate) fn token_maps<'a>(
+    tokens: impl IntoIterator<Item = &'a str>,
+) -> Result<(IndexMap<String, u16>, Vec<MSu16xNU>), Error> {
+    let mut token_frequencies: IndexMap<String, u16> = IndexMap::new();
+    let vertices: Vec<MSu16xNU> = tokens
+        .into_iter()
+        .map(|token| {
+            let entry = token_frequencies.entry(token.to_string());
+            let label = entry.index();
+            if label >= MSu16xNU::len() {
+                return Err(invalid_data(format!("Sample uses more than the {} labels supported", MSu16xNU::len())))
+            }
+            entry.and_modify(|f| *f += 1).or_insert(1);
             let mut set = MSu16xNU::empty();
             set.insert(label, 1);
-            set
+            Ok(set)
         })
-        .collect();
-    (char_frequencies, vertices)
+        .collect::<Result<_, Error>>()?;
+    Ok((token_frequencies, vertices))
+}
+
+fn dimensions(rows: &[Vec<&str>]) -> (usize, usize) {
+    (rows.first().map_or(0, Vec::len), rows.len())
 }
 
-fn make_edges(string: &str, intercardinals: bool) -> Edges {
-    let width = string.chars().position(|char| char == '\n').unwrap();
-    let height = string.split('\n').filter(|l| !l.is_empty()).count();
+fn make_edges(rows: &[Vec<&str>], intercardinals: bool) -> Edges {
+    let (width, height) = dimensions(rows);
     if intercardinals {
         make_edges_8_way_grid(width, height)
     } else {
@@ -46,37 +318,122 @@ fn make_edges(string: &str, intercardinals: bool) -> Edges {
     }
 }
 
-const CONTRADICT_CHAR: char = '❌';
+pub(crate) const CONTRADICT_TOKEN: &str = "❌";
 
-pub fn render(
-    filename: &str,
-    graph: Graph,
-    key: &IndexMap<char, u16>,
+/// How vertices with more than one remaining label are rendered, contradictions are always ❌.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Undecided {
+    /// A fixed glyph, by default the template wildcard so partial outputs can be used as templates.
+    Placeholder(String),
+    /// The number of remaining labels, or + for more than 9.
+    Count,
+    /// The glyph of the most frequent remaining label.
+    Likely,
+}
+
+impl Default for Undecided {
+    fn default() -> Self {
+        Undecided::Placeholder(WILDCARD.to_string())
+    }
+}
+
+// the token of a vertex
+pub(crate) fn vertex_token<'a>(
+    labels: &MSu16xNU,
+    key: &'a IndexMap<String, u16>,
+    undecided: &'a Undecided,
+) -> Cow<'a, str> {
+    let glyph = |label: usize| key.get_index(label).map_or(CONTRADICT_TOKEN, |t| t.0.as_str());
+    match labels.count_non_zero() {
+        0 => Cow::Borrowed(CONTRADICT_TOKEN),
+        1 => Cow::Borrowed(glyph(labels.imax())),
+        count => match undecided {
+            Undecided::Placeholder(placeholder) => Cow::Borrowed(placeholder),
+            Undecided::Count if count > 9 => Cow::Borrowed("+"),
+            Undecided::Count => Cow::Owned(count.to_string()),
+            Undecided::Likely => Cow::Borrowed(glyph(labels.imax())),
+        }
+    }
+}
+
+fn render_lines(
+    vertices: &[MSu16xNU],
+    key: &IndexMap<String, u16>,
     width: usize,
-) {
-    let lines: String = graph
-        .vertices
+    tokens: Tokens,
+    undecided: &Undecided,
+) -> String {
+    vertices
         .chunks_exact(width)
         .map(|chunk| {
             chunk
                 .iter()
-                .map(|labels| {
-                    labels
-                        .is_singleton()
-                        .then(|| key.get_index(labels.imax()).map(|t| *t.0))
-                        .flatten()
-                        .unwrap_or(CONTRADICT_CHAR)
-                })
-                .chain(iter::once('\n'))
+                .map(|labels| vertex_token(labels, key, undecided))
+                .collect::<Vec<Cow<str>>>()
+                .join(tokens.separator())
         })
-        .flatten()
-        .collect::<String>();
+        .flat_map(|line| iter::once(line).chain(iter::once("\n".to_string())))
+        .collect::<String>()
+}
+
+pub fn render(
+    filename: &str,
+    graph: Graph,
+    key: &IndexMap<String, u16>,
+    width: usize,
+) {
+    render_tokens(filename, graph, key, width, Tokens::Graphemes, &Undecided::default())
+}
+
+pub fn render_tokens(
+    filename: &str,
+    graph: Graph,
+    key: &IndexMap<String, u16>,
+    width: usize,
+    tokens: Tokens,
+    undecided: &Undecided,
+) {
+    let lines = render_lines(&graph.vertices, key, width, tokens, undecided);
     if write(filename, lines).is_ok() {}
 }
 
+/// Render the frames of `collapse_progress`, to numbered files next to `filename` or, with
+/// `single_file`, to `filename` with each frame under a header line.
+pub fn progress(
+    filename: &str,
+    frames: Vec<Vertices>,
+    key: &IndexMap<String, u16>,
+    width: usize,
+    tokens: Tokens,
+    undecided: &Undecided,
+    single_file: bool,
+) {
+    let output_frames = frames.len();
+    let rendered = frames
+        .iter()
+        .enumerate()
+        .map(|(frame, vertices)| {
+            (pad_frame(output_frames, frame), render_lines(vertices, key, width, tokens, undecided))
+        });
+
+    if single_file {
+        let lines: String = rendered
+            .map(|(frame, lines)| format!("=== frame {} ===\n{}", frame, lines))
+            .collect();
+        if write(filename, lines).is_ok() {}
+    } else {
+        let (file_title, file_extension) = filename.rsplit_once('.').unwrap_or((filename, "txt"));
+        rendered.for_each(|(frame, lines)| {
+            if write(format!("{}_{}.{}", file_title, frame, file_extension), lines).is_ok() {}
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::ErrorKind;
+    use crate::wfc::collapse::{collapse, collapse_progress};
 
     #[test]
     fn test_parse_easy() {
@@ -93,4 +450,238 @@ mod tests {
             assert_eq!(graph.vertices.len(), 108);
         }
     }
+
+    #[test]
+    fn test_parse_multiple() {
+        let filenames = ["resources/test/easy_emoji.txt", "resources/test/medium_emoji.txt"];
+        let (rules, all_labels, keys) = parse_multiple(&filenames, true).unwrap();
+        let (easy, easy_keys) = parse(filenames[0], true).unwrap();
+        let (medium, medium_keys) = parse(filenames[1], true).unwrap();
+
+        // every key of both samples appears once in the shared keys, with summed frequencies
+        let mut union: Vec<&String> = easy_keys.keys().chain(medium_keys.keys()).collect();
+        union.sort_unstable();
+        union.dedup();
+        assert_eq!(keys.len(), union.len());
+        keys.iter().for_each(|(key, frequency)| {
+            let expected = easy_keys.get(key).unwrap_or(&0) + medium_keys.get(key).unwrap_or(&0);
+            assert_eq!(*frequency, expected);
+        });
+        assert_eq!(all_labels, keys.values().collect());
+
+        // the first sample keeps its label indices, so its rules are contained in the merged rules
+        easy.rules().iter().for_each(|(key, labels)| {
+            assert!(labels.is_subset(rules.get(key).unwrap()));
+        });
+        assert!(rules.len() >= medium.rules().len());
+    }
+
+    #[test]
+    fn test_parse_stencil() {
+        let stencil = Stencil::ring(2);
+        let (graph, keys) = parse_stencil("resources/test/medium_emoji.txt", &stencil).unwrap();
+        assert_eq!(keys.len(), 7);
+        assert_eq!(graph.vertices.len(), 108);
+        assert!(graph.rules().keys().all(|(direction, _)| (*direction as usize) < stencil.offsets().len()));
+    }
+
+    #[test]
+    fn test_parse_graphemes() {
+        let (graph, keys) = parse("resources/test/grapheme_sample.txt", false).unwrap();
+        // astronaut and thumbs up are zero width joined and skin toned sequences, é has a combining accent
+        let expected = vec!["🇯🇵", "👩‍🚀", "👍🏽", "e\u{301}", "🌳"];
+        assert_eq!(keys.keys().collect::<Vec<_>>(), expected);
+        assert_eq!(graph.vertices.len(), 12);
+        assert_eq!(keys["🌳"], 6);
+    }
+
+    #[test]
+    fn test_parse_words() {
+        let (graph, keys) = parse_tokens("resources/test/word_sample.txt", false, Tokens::Words).unwrap();
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec!["grass", "road", "water"]);
+        assert_eq!(graph.vertices.len(), 9);
+        // grass is to the west of road
+        let road = keys.get_index_of("road").unwrap();
+        assert!(graph.rules().get(&(3, road)).unwrap().into_iter().next().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        for (filename, tokens) in [
+            ("resources/test/grapheme_sample.txt", Tokens::Graphemes),
+            ("resources/test/word_sample.txt", Tokens::Words),
+        ].iter() {
+            let (graph, keys) = parse_tokens(filename, false, *tokens).unwrap();
+            let output = format!("{}.render_test", filename);
+            render_tokens(&output, graph, &keys, 3, *tokens, &Undecided::default());
+            let rendered = read_to_string(&output).unwrap();
+            std::fs::remove_file(&output).unwrap();
+            assert_eq!(rendered, read_to_string(filename).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_crlf() {
+        let filename = "resources/test/crlf_test.txt";
+        write(filename, "ab\r\nba\r\n").unwrap();
+        let parsed = parse(filename, false);
+        std::fs::remove_file(filename).unwrap();
+
+        let (graph, keys) = parsed.unwrap();
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(graph.vertices.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = [
+            ("empty", ""),
+            ("newline", "abc"),
+            ("blank", "\n\n"),
+            ("ragged", "abc\nab\nabc\n"),
+            ("labels", "abcdefghijklm\nnopqrstuvwxyz\n"),
+        ];
+        for (name, sample) in errors.iter() {
+            let filename = format!("resources/test/parse_error_{}.txt", name);
+            write(&filename, sample).unwrap();
+            let parsed = parse(&filename, false);
+            std::fs::remove_file(&filename).unwrap();
+            assert_eq!(parsed.unwrap_err().kind(), ErrorKind::InvalidData, "{}", name);
+        }
+
+        let filename = "resources/test/parse_error_message.txt";
+        write(filename, "abc\nab\n").unwrap();
+        let parsed = parse(filename, false);
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(parsed.unwrap_err().to_string(), "Line 2 is 2 tokens wide but the first line is 3 tokens wide");
+    }
+
+    #[test]
+    fn test_transform_rows() {
+        let augment = Augment::box_drawing();
+        let rows = vec![vec!["┌", "─"], vec!["│", "a"], vec!["└", "→"]];
+
+        let rotated = rotate_rows(&rows, &augment.rotations);
+        assert_eq!(rotated, vec![vec!["┌", "─", "┐"], vec!["↓", "a", "│"]]);
+        let mirrored = mirror_rows(&rows, &augment.mirrors);
+        assert_eq!(mirrored, vec![vec!["─", "┐"], vec!["a", "│"], vec!["←", "┘"]]);
+
+        // four rotations, or mirroring twice, give the sample back
+        let full_turn = (0..4).fold(rows.clone(), |rows, _| rotate_rows(&rows, &augment.rotations));
+        assert_eq!(full_turn, rows);
+        assert_eq!(mirror_rows(&mirrored, &augment.mirrors), rows);
+    }
+
+    #[test]
+    fn test_parse_augmented() {
+        let filename = "resources/test/box_sample.txt";
+        let (rules, all_labels, keys) = parse_augmented(filename, false, &Augment::default()).unwrap();
+        let (graph, sample_keys) = parse(filename, false).unwrap();
+        assert_eq!(keys, sample_keys);
+        assert_eq!(all_labels, graph.all_labels);
+        assert_eq!(rules, graph.rules());
+
+        let (rules, all_labels, keys) = parse_augmented(filename, false, &Augment::box_drawing()).unwrap();
+        // the sample has ┌ but no ┘, which its rotations add
+        assert!(!sample_keys.contains_key("┘"));
+        assert!(keys.contains_key("┘"));
+        // eight copies of the sample
+        assert_eq!(keys.values().map(|f| *f as usize).sum::<usize>(), graph.vertices.len() * 8);
+        assert_eq!(all_labels, keys.values().collect());
+        // ┘ takes ─ to its west and │ to its north
+        let corner = keys.get_index_of("┘").unwrap();
+        let (horizontal, vertical) = (keys.get_index_of("─").unwrap(), keys.get_index_of("│").unwrap());
+        assert!(rules.get(&(3, corner)).unwrap().into_iter().nth(horizontal).unwrap() > 0);
+        assert!(rules.get(&(1, corner)).unwrap().into_iter().nth(vertical).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_parse_augmented_large() {
+        // eight copies of 100 x 100 cells count more than u16::MAX of each glyph
+        let filename = std::env::temp_dir().join("wfc_rust_large_sample.txt");
+        std::fs::write(&filename, format!("{}\n", "#".repeat(100)).repeat(100)).unwrap();
+        let (rules, all_labels, keys) =
+            parse_augmented(filename.to_str().unwrap(), false, &Augment::box_drawing()).unwrap();
+        assert_eq!(keys.get("#"), Some(&u16::MAX));
+        assert_eq!(all_labels.into_iter().next(), Some(u16::MAX));
+        assert!(rules.values().all(|labels| labels.into_iter().next() == Some(u16::MAX)));
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let (sample, keys) = parse("resources/test/box_sample.txt", false).unwrap();
+        let (template, width) = parse_template("resources/test/box_template.txt", &keys, false).unwrap();
+        let label = |glyph: &str| keys.get_index_of(glyph).unwrap();
+
+        assert_eq!(width, 5);
+        assert_eq!(template.vertices.len(), 15);
+        assert_eq!(template.all_labels, sample.all_labels);
+        assert!(template.vertices[0].is_singleton());
+        assert_eq!(template.vertices[0].imax(), label("┌"));
+        assert_eq!(template.vertices[2], sample.all_labels);
+        let mut set = MSu16xNU::empty();
+        set.insert(label("."), keys["."]);
+        set.insert(label("│"), keys["│"]);
+        assert_eq!(template.vertices[7], set);
+
+        // pinned cells are kept by collapse and rendered back
+        let collapsed = collapse(&sample.rules(), &template, Some(3), None);
+        let filename = "resources/test/box_template_output.txt";
+        render(filename, collapsed, &keys, width);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        let rows = rows(&rendered, Tokens::Graphemes).unwrap();
+        assert_eq!((rows[0][0], rows[0][1], rows[1][0], rows[1][1]), ("┌", "─", "│", "."));
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let keys: IndexMap<String, u16> = [("a".to_string(), 1)].iter().cloned().collect();
+        let all_labels: MSu16xNU = [1].iter().collect();
+
+        assert_eq!(template_cells("a[ab]?").unwrap(), vec!["a", "[ab]", "?"]);
+        assert!(template_cells("a[b").is_err());
+        assert!(template_labels("[]", &keys, &all_labels).is_err());
+        assert!(template_labels("b", &keys, &all_labels).is_err());
+        assert_eq!(template_labels("?", &keys, &all_labels).unwrap(), all_labels);
+    }
+
+    #[test]
+    fn test_undecided_tokens() {
+        let keys: IndexMap<String, u16> = [("a".to_string(), 1), ("b".to_string(), 3)].iter().cloned().collect();
+        let both: MSu16xNU = [1, 3].iter().collect();
+        let a: MSu16xNU = [1, 0].iter().collect();
+
+        let placeholder = Undecided::Placeholder("·".to_string());
+        assert_eq!(vertex_token(&both, &keys, &Undecided::default()), "?");
+        assert_eq!(vertex_token(&both, &keys, &placeholder), "·");
+        assert_eq!(vertex_token(&both, &keys, &Undecided::Count), "2");
+        assert_eq!(vertex_token(&both, &keys, &Undecided::Likely), "b");
+        assert_eq!(vertex_token(&a, &keys, &Undecided::Count), "a");
+        assert_eq!(vertex_token(&MSu16xNU::empty(), &keys, &Undecided::Likely), CONTRADICT_TOKEN);
+    }
+
+    #[test]
+    fn test_progress() {
+        let (graph, keys) = parse("resources/test/box_sample.txt", false).unwrap();
+        let output = Graph::new(vec![graph.all_labels; 12], make_edges_cardinal_grid(4, 3), graph.all_labels);
+        let frames = collapse_progress(&graph.rules(), output, Some(2));
+        let frames_len = frames.len();
+        assert!(frames_len > 1);
+
+        let filename = "resources/test/text_progress_test.txt";
+        progress(filename, frames.clone(), &keys, 4, Tokens::Graphemes, &Undecided::Count, true);
+        let rendered = read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(rendered.lines().count(), frames_len * 4);
+        assert!(rendered.starts_with(&format!("=== frame {} ===\n", pad_frame(frames_len, 0))));
+
+        let filename = "resources/test/text_progress_test.txt";
+        progress(filename, frames, &keys, 4, Tokens::Graphemes, &Undecided::default(), false);
+        (0..frames_len).for_each(|frame| {
+            let frame_name = format!("resources/test/text_progress_test_{}.txt", pad_frame(frames_len, frame));
+            assert_eq!(read_to_string(&frame_name).unwrap().lines().count(), 3);
+            std::fs::remove_file(&frame_name).unwrap();
+        });
+    }
 }
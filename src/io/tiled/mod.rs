@@ -0,0 +1,3 @@
+pub mod tiled_parser;
+pub mod socket_parser;
+pub mod tiled_renderer;
@@ -0,0 +1,246 @@
+use crate::graph::graph::{EdgeDirection, Rules, insert_rule};
+use crate::io::tiled::tiled_parser::TileSet;
+use crate::io::utils::invalid_data;
+use crate::MSu16xNU;
+use hashbrown::{HashMap, HashSet};
+use image::imageops;
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::io::Error;
+use std::path::Path;
+
+pub const SOCKETS_FILE: &str = "sockets.toml";
+
+// cardinal grid direction ids of the sides north, east, south and west
+const SIDE_DIRECTIONS: [EdgeDirection; 4] = [1, 4, 6, 3];
+
+// sockets of the sides north, east, south and west, each read clockwise around the tile
+type Sockets = [String; 4];
+
+/// A generated variant of a tile: its image mirrored horizontally if `mirrored`,
+/// then rotated 90 degrees counter-clockwise `rotations` times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileVariant {
+    pub tile: String,
+    pub rotations: usize,
+    pub mirrored: bool,
+}
+
+impl TileVariant {
+    /// mxgmn style name, "corner 5" is corner mirrored and rotated once.
+    pub fn name(&self) -> String {
+        format!("{} {}", self.tile, self.rotations + if self.mirrored { 4 } else { 0 })
+    }
+}
+
+#[derive(Deserialize)]
+struct TileEntry {
+    name: String,
+    sockets: Sockets,
+    #[serde(default)]
+    rotate: bool,
+    #[serde(default)]
+    flip: bool,
+    #[serde(default = "default_weight")]
+    weight: u16,
+}
+
+fn default_weight() -> u16 { 1 }
+
+#[derive(Deserialize)]
+struct SocketFile {
+    tiles: Vec<TileEntry>,
+    // sockets whose profile is not mirror symmetric
+    #[serde(default)]
+    asymmetric: Vec<String>,
+}
+
+/*
+Load a socket tileset directory holding a sockets.toml and a {name}.png image for every tile:
+
+asymmetric = ["fence"]  # optional
+
+[[tiles]]
+name = "corner"
+sockets = ["road", "road", "grass", "fence"]  # north, east, south, west
+rotate = true  # optional, add the rotations of the tile
+flip = true    # optional, add the mirror images of the tile and its rotations
+weight = 2     # optional, defaults to 1
+
+Two sides fit when their sockets are equal. An asymmetric socket is read clockwise around
+its tile and fits only its mirror image, marked with a trailing ', so "fence" fits "fence'".
+Variants with the same sockets as an earlier variant of the same tile are skipped.
+*/
+pub fn parse(directory: &str) -> Result<(Rules, MSu16xNU, TileSet), Error> {
+    let directory = Path::new(directory);
+    let (rules, all_labels, variants) = parse_str(&read_to_string(directory.join(SOCKETS_FILE))?)?;
+
+    let mut images = HashMap::new();
+    let mut tile_set = TileSet { names: Vec::new(), bitmaps: Vec::new(), tile_size: 0 };
+    for variant in variants {
+        if !images.contains_key(&variant.tile) {
+            let path = directory.join(format!("{}.png", variant.tile));
+            let image = image::open(&path)
+                .map_err(|e| invalid_data(format!("Could not open {}: {}", path.display(), e)))?
+                .to_rgb8();
+            if image.width() != image.height() || (tile_set.tile_size != 0 && image.width() != tile_set.tile_size) {
+                return Err(invalid_data(format!("Tile {} is not a square of the tileset's tile size", variant.tile)))
+            }
+            tile_set.tile_size = image.width();
+            images.insert(variant.tile.clone(), image);
+        }
+        let image = &images[&variant.tile];
+        let mirrored = if variant.mirrored { imageops::flip_horizontal(image) } else { image.clone() };
+        let bitmap = (0..variant.rotations).fold(mirrored, |bitmap, _| imageops::rotate270(&bitmap));
+        tile_set.names.push(variant.name());
+        tile_set.bitmaps.push(bitmap);
+    }
+
+    Ok((rules, all_labels, tile_set))
+}
+
+/// Derive rules from a sockets.toml string, the index of a variant is its label.
+pub fn parse_str(string: &str) -> Result<(Rules, MSu16xNU, Vec<TileVariant>), Error> {
+    let file: SocketFile = toml::from_str(string).map_err(|e| invalid_data(e.to_string()))?;
+    let asymmetric: HashSet<&str> = file.asymmetric.iter().map(|socket| socket.as_str()).collect();
+
+    let mut names = HashSet::new();
+    let mut variants: Vec<(TileVariant, Sockets, u16)> = Vec::new();
+    for entry in &file.tiles {
+        if !names.insert(&entry.name) {
+            return Err(invalid_data(format!("Tile {} is declared more than once", entry.name)))
+        }
+        let mirrors: &[bool] = if entry.flip { &[false, true] } else { &[false] };
+        let rotations = if entry.rotate { 4 } else { 1 };
+        let mut seen: Vec<Sockets> = Vec::new();
+        for mirrored in mirrors {
+            let mut sockets = if *mirrored { mirror(&entry.sockets, &asymmetric) } else { entry.sockets.clone() };
+            for rotation in 0..rotations {
+                if !seen.contains(&sockets) {
+                    seen.push(sockets.clone());
+                    let variant = TileVariant { tile: entry.name.clone(), rotations: rotation, mirrored: *mirrored };
+                    variants.push((variant, sockets.clone(), entry.weight));
+                }
+                sockets = rotate(&sockets);
+            }
+        }
+    }
+
+    if variants.len() > MSu16xNU::len() {
+        return Err(invalid_data(format!(
+            "Tileset has {} tile variants, more than the {} labels supported", variants.len(), MSu16xNU::len()
+        )))
+    }
+
+    let mut rules: Rules = HashMap::new();
+    for (from, (_, from_sockets, _)) in variants.iter().enumerate() {
+        for (to, (_, to_sockets, weight)) in variants.iter().enumerate() {
+            for side in 0..4 {
+                let socket = &from_sockets[side];
+                if to_sockets[(side + 2) % 4] == reverse(socket, &asymmetric) {
+                    insert_rule(&mut rules, SIDE_DIRECTIONS[side], from, to, *weight);
+                }
+            }
+        }
+    }
+
+    let all_labels = variants.iter().map(|(_, _, weight)| weight).collect();
+
+    Ok((rules, all_labels, variants.into_iter().map(|(variant, _, _)| variant).collect()))
+}
+
+// the socket read in the opposite direction
+fn reverse(socket: &str, asymmetric: &HashSet<&str>) -> String {
+    match socket.strip_suffix('\'') {
+        Some(base) => base.to_string(),
+        None if asymmetric.contains(socket) => format!("{}'", socket),
+        None => socket.to_string()
+    }
+}
+
+// sockets of the tile rotated 90 degrees counter-clockwise
+fn rotate(sockets: &Sockets) -> Sockets {
+    let [north, east, south, west] = sockets.clone();
+    [east, south, west, north]
+}
+
+// sockets of the tile mirrored horizontally, mirroring reverses every side
+fn mirror(sockets: &Sockets, asymmetric: &HashSet<&str>) -> Sockets {
+    let [north, east, south, west] = sockets;
+    [
+        reverse(north, asymmetric),
+        reverse(west, asymmetric),
+        reverse(south, asymmetric),
+        reverse(east, asymmetric),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::tiled::tiled_parser;
+
+    fn allows(labels: &MSu16xNU, label: usize) -> bool {
+        let mut set = MSu16xNU::empty();
+        set.insert(label, 1);
+        !labels.intersection(&set).is_empty()
+    }
+
+    #[test]
+    fn test_parse_sockets() {
+        let (rules, all_labels, tile_set) = parse("resources/test/tiles").unwrap();
+
+        // the same variants in the same order as the symmetry class tileset
+        let (_, symmetry_labels, symmetry_tile_set) = tiled_parser::parse("resources/test/tiles").unwrap();
+        assert_eq!(tile_set.names, symmetry_tile_set.names);
+        assert_eq!(tile_set.bitmaps, symmetry_tile_set.bitmaps);
+        assert_eq!(all_labels, symmetry_labels);
+
+        // corner 0 connects north and east so takes corner 1 to its east, but not corner 0
+        assert!(allows(rules.get(&(4, 3)).unwrap(), 4));
+        assert!(!allows(rules.get(&(4, 3)).unwrap(), 3));
+        // line 0 is vertical: line 0 fits to its north, tiles without a west pipe fit to its east
+        assert!(allows(rules.get(&(1, 1)).unwrap(), 1));
+        assert!(!allows(rules.get(&(1, 1)).unwrap(), 2));
+        assert_eq!(rules.get(&(4, 1)).unwrap(), &[4, 2, 0, 1, 0, 0, 1, 0, 1, 0, 0].iter().collect());
+    }
+
+    #[test]
+    fn test_asymmetric_sockets() {
+        let sockets_file = r#"
+            asymmetric = ["fence"]
+
+            [[tiles]]
+            name = "a"
+            sockets = ["fence", "fence", "fence", "fence"]
+
+            [[tiles]]
+            name = "b"
+            sockets = ["grass", "fence'", "grass", "grass"]
+            flip = true
+        "#;
+        let (rules, all_labels, variants) = parse_str(sockets_file).unwrap();
+
+        assert_eq!(variants.iter().map(|variant| variant.name()).collect::<Vec<_>>(), vec!["a 0", "b 0", "b 4"]);
+        assert_eq!(all_labels, [1, 1, 1].iter().collect());
+        // an asymmetric socket does not fit itself
+        assert_eq!(rules.get(&(4, 0)), None);
+        assert_eq!(rules.get(&(3, 0)), Some(&[0, 1, 0].iter().collect()));
+        assert_eq!(rules.get(&(4, 1)), Some(&[1, 0, 1].iter().collect()));
+        // mirroring b reverses its fence, which then fits the fence of b
+        assert_eq!(rules.get(&(3, 2)), Some(&[0, 1, 0].iter().collect()));
+    }
+
+    #[test]
+    fn test_socket_transforms() {
+        let asymmetric: HashSet<&str> = ["b"].iter().copied().collect();
+        let sockets: Sockets = ["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()];
+
+        assert_eq!(rotate(&rotate(&rotate(&rotate(&sockets)))), sockets);
+        assert_eq!(mirror(&mirror(&sockets, &asymmetric), &asymmetric), sockets);
+        assert_eq!(mirror(&sockets, &asymmetric), ["a".to_string(), "d".to_string(), "c".to_string(), "b'".to_string()]);
+        assert_eq!(rotate(&sockets), ["b".to_string(), "c".to_string(), "d".to_string(), "a".to_string()]);
+        assert_eq!(reverse("b", &asymmetric), "b'");
+        assert_eq!(reverse("a", &asymmetric), "a");
+    }
+}
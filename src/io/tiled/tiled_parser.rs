@@ -0,0 +1,281 @@
+use crate::graph::graph::{EdgeDirection, Rules, insert_rule};
+use crate::io::utils::invalid_data;
+use crate::MSu16xNU;
+use hashbrown::HashMap;
+use image::{imageops, RgbImage};
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::io::Error;
+use std::path::Path;
+
+pub const TILESET_FILE: &str = "tileset.toml";
+
+// cardinal grid direction ids, see `make_edges_cardinal_grid`
+const NORTH: EdgeDirection = 1;
+const WEST: EdgeDirection = 3;
+const EAST: EdgeDirection = 4;
+const SOUTH: EdgeDirection = 6;
+
+/// The tiles of a parsed tileset, the index of a variant is its label.
+#[derive(Debug, Clone)]
+pub struct TileSet {
+    // variant names, e.g. "corner 2" for the third variant of corner
+    pub names: Vec<String>,
+    pub bitmaps: Vec<RgbImage>,
+    pub tile_size: u32,
+}
+
+/*
+Symmetry classes of mxgmn's simple tiled model. Variant i of a tile is its image rotated
+90 degrees counter-clockwise i times, a rotates a variant 90 degrees counter-clockwise and
+b mirrors it horizontally.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Symmetry { X, I, Backslash, T, L }
+
+impl Symmetry {
+    fn from_name(name: &str) -> Option<Symmetry> {
+        match name {
+            "X" => Some(Symmetry::X),
+            "I" => Some(Symmetry::I),
+            "\\" => Some(Symmetry::Backslash),
+            "T" => Some(Symmetry::T),
+            "L" => Some(Symmetry::L),
+            _ => None
+        }
+    }
+
+    fn cardinality(self) -> usize {
+        match self {
+            Symmetry::X => 1,
+            Symmetry::I | Symmetry::Backslash => 2,
+            Symmetry::T | Symmetry::L => 4,
+        }
+    }
+
+    fn a(self, i: usize) -> usize {
+        match self {
+            Symmetry::X => i,
+            Symmetry::I | Symmetry::Backslash => 1 - i,
+            Symmetry::T | Symmetry::L => (i + 1) % 4,
+        }
+    }
+
+    fn b(self, i: usize) -> usize {
+        match self {
+            Symmetry::X | Symmetry::I => i,
+            Symmetry::Backslash => 1 - i,
+            Symmetry::T => (4 - i) % 4,
+            Symmetry::L => i ^ 1,
+        }
+    }
+
+    // the variant of action r applied to variant i, r < 4 is a^r and r >= 4 is b(a^(r - 4))
+    fn act(self, i: usize, r: usize) -> usize {
+        let rotated = (0..r % 4).fold(i, |i, _| self.a(i));
+        if r < 4 { rotated } else { self.b(rotated) }
+    }
+}
+
+#[derive(Deserialize)]
+struct TileEntry {
+    name: String,
+    #[serde(default = "default_symmetry")]
+    symmetry: String,
+    #[serde(default = "default_weight")]
+    weight: u16,
+}
+
+fn default_symmetry() -> String { "X".to_string() }
+
+fn default_weight() -> u16 { 1 }
+
+#[derive(Deserialize)]
+struct NeighbourEntry {
+    left: String,
+    right: String,
+}
+
+#[derive(Deserialize)]
+struct TileSetFile {
+    tiles: Vec<TileEntry>,
+    #[serde(default)]
+    neighbours: Vec<NeighbourEntry>,
+}
+
+struct Tile {
+    symmetry: Symmetry,
+    // label of variant 0
+    first: usize,
+}
+
+/*
+Load a tileset directory holding a tileset.toml and a {name}.png image for every tile:
+
+[[tiles]]
+name = "corner"
+symmetry = "L"  # one of X, I, \, T, L, optional, defaults to X
+weight = 2      # optional, defaults to 1
+
+[[neighbours]]  # right may be placed to the east of left
+left = "corner"
+right = "corner 1"
+
+A tile reference "name r" is action r applied to the tile image, as in mxgmn's tilesets.
+Rules in the other cardinal directions are derived by rotating and mirroring each pair.
+*/
+pub fn parse(directory: &str) -> Result<(Rules, MSu16xNU, TileSet), Error> {
+    let directory = Path::new(directory);
+    let string = read_to_string(directory.join(TILESET_FILE))?;
+    let file: TileSetFile = toml::from_str(&string).map_err(|e| invalid_data(e.to_string()))?;
+
+    let mut tiles: HashMap<String, Tile> = HashMap::new();
+    let mut tile_set = TileSet { names: Vec::new(), bitmaps: Vec::new(), tile_size: 0 };
+    let mut weights: Vec<u16> = Vec::new();
+    for entry in &file.tiles {
+        if tiles.contains_key(&entry.name) {
+            return Err(invalid_data(format!("Tile {} is declared more than once", entry.name)))
+        }
+        let symmetry = Symmetry::from_name(&entry.symmetry)
+            .ok_or_else(|| invalid_data(format!("Unknown symmetry {} of tile {}", entry.symmetry, entry.name)))?;
+
+        let path = directory.join(format!("{}.png", entry.name));
+        let bitmap = image::open(&path)
+            .map_err(|e| invalid_data(format!("Could not open {}: {}", path.display(), e)))?
+            .to_rgb8();
+        if bitmap.width() != bitmap.height() || (tile_set.tile_size != 0 && bitmap.width() != tile_set.tile_size) {
+            return Err(invalid_data(format!("Tile {} is not a square of the tileset's tile size", entry.name)))
+        }
+        tile_set.tile_size = bitmap.width();
+
+        tiles.insert(entry.name.clone(), Tile { symmetry, first: weights.len() });
+        (0..symmetry.cardinality()).fold(bitmap, |bitmap, variant| {
+            tile_set.names.push(format!("{} {}", entry.name, variant));
+            tile_set.bitmaps.push(bitmap.clone());
+            weights.push(entry.weight);
+            imageops::rotate270(&bitmap)
+        });
+    }
+
+    if weights.len() > MSu16xNU::len() {
+        return Err(invalid_data(format!(
+            "Tileset has {} tile variants, more than the {} labels supported", weights.len(), MSu16xNU::len()
+        )))
+    }
+
+    let mut rules: Rules = HashMap::new();
+    for entry in &file.neighbours {
+        let (left, left_variant) = tile_reference(&tiles, &entry.left)?;
+        let (right, right_variant) = tile_reference(&tiles, &entry.right)?;
+        // apply every rotation and reflection of the square to the pair
+        for mirror in [false, true].iter() {
+            for rotations in 0..4 {
+                let transform = |tile: &Tile, variant: usize| {
+                    let variant = if *mirror { tile.symmetry.b(variant) } else { variant };
+                    tile.first + tile.symmetry.act(variant, rotations)
+                };
+                let from = transform(left, left_variant);
+                let to = transform(right, right_variant);
+                let direction = (0..rotations).fold(if *mirror { WEST } else { EAST }, |d, _| rotate(d));
+                insert_rule(&mut rules, direction, from, to, weights[to]);
+                insert_rule(&mut rules, 7 - direction, to, from, weights[from]);
+            }
+        }
+    }
+
+    let all_labels = weights.iter().collect();
+
+    Ok((rules, all_labels, tile_set))
+}
+
+// rotate a cardinal direction 90 degrees counter-clockwise
+fn rotate(direction: EdgeDirection) -> EdgeDirection {
+    match direction {
+        EAST => NORTH,
+        NORTH => WEST,
+        WEST => SOUTH,
+        _ => EAST,
+    }
+}
+
+// "name" or "name r" to the tile and its variant
+fn tile_reference<'a>(tiles: &'a HashMap<String, Tile>, reference: &str) -> Result<(&'a Tile, usize), Error> {
+    let mut parts = reference.split_whitespace();
+    let name = parts.next().unwrap_or("");
+    let action = match parts.next() {
+        Some(action) => action
+            .parse::<usize>()
+            .ok()
+            .filter(|action| *action < 8)
+            .ok_or_else(|| invalid_data(format!("Invalid tile action in {}", reference)))?,
+        None => 0
+    };
+    let tile = tiles.get(name).ok_or_else(|| invalid_data(format!("Unknown tile: {}", name)))?;
+    Ok((tile, tile.symmetry.act(0, action)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    fn allows(labels: &MSu16xNU, label: usize) -> bool {
+        let mut set = MSu16xNU::empty();
+        set.insert(label, 1);
+        !labels.intersection(&set).is_empty()
+    }
+
+    #[test]
+    fn test_symmetry_actions() {
+        // a four times and b twice are the identity
+        for symmetry in [Symmetry::X, Symmetry::I, Symmetry::Backslash, Symmetry::T, Symmetry::L].iter() {
+            for i in 0..symmetry.cardinality() {
+                assert_eq!(symmetry.act(i, 4), symmetry.b(i));
+                assert_eq!((0..4).fold(i, |i, _| symmetry.a(i)), i);
+                assert_eq!(symmetry.b(symmetry.b(i)), i);
+            }
+        }
+        assert_eq!(Symmetry::L.act(0, 4), 1);
+        assert_eq!(Symmetry::T.act(0, 5), 3);
+    }
+
+    #[test]
+    fn test_parse_tileset() {
+        let (rules, all_labels, tile_set) = parse("resources/test/tiles").unwrap();
+
+        // empty, line 0-1, corner 0-3, t 0-3
+        assert_eq!(tile_set.names.len(), 11);
+        assert_eq!(tile_set.names[4], "corner 1");
+        assert_eq!(tile_set.tile_size, 3);
+        assert_eq!(all_labels, [4, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1].iter().collect());
+
+        // line 1 is line rotated, a horizontal line
+        assert_eq!(tile_set.bitmaps[2], imageops::rotate270(&tile_set.bitmaps[1]));
+
+        // corner 0 connects north and east so takes corner 1 to its east
+        let corner_east = rules.get(&(EAST, 3)).unwrap();
+        assert!(allows(corner_east, 4));
+        assert!(!allows(corner_east, 3));
+        // the vertical line continues north, derived from the horizontal line continuing east
+        assert!(allows(rules.get(&(NORTH, 1)).unwrap(), 1));
+        assert!(!allows(rules.get(&(NORTH, 1)).unwrap(), 2));
+        // every rule has its reverse
+        rules.iter().for_each(|((direction, from), labels)| {
+            labels
+                .into_iter()
+                .enumerate()
+                .filter(|(_, frequency)| frequency > &0)
+                .for_each(|(to, _)| assert!(allows(rules.get(&(7 - direction, to)).unwrap(), *from)))
+        });
+    }
+
+    #[test]
+    fn test_parse_tileset_errors() {
+        let mut tiles: HashMap<String, Tile> = HashMap::new();
+        tiles.insert("t".to_string(), Tile { symmetry: Symmetry::T, first: 0 });
+        assert_eq!(tile_reference(&tiles, "t 5").unwrap().1, 3);
+        assert!(tile_reference(&tiles, "t 8").is_err());
+        assert!(tile_reference(&tiles, "s").is_err());
+        assert_eq!(parse("resources/test/missing").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::graph::graph::Graph;
+use crate::io::tiled::tiled_parser::TileSet;
+use crate::utils::index_to_coords;
+use image::{imageops, Rgb, RgbImage};
+
+const CONTRADICT_COLOUR: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// Composite the tile bitmaps of a graph collapsed from a tileset, `width` is in tiles.
+pub fn render(filename: &str, graph: Graph, tile_set: &TileSet, width: usize) {
+    render_image(graph, tile_set, width).save(filename).unwrap();
+}
+
+/// Vertices that are not collapsed show the average of their possible tiles.
+pub fn render_image(graph: Graph, tile_set: &TileSet, width: usize) -> RgbImage {
+    let depth = graph.vertices.len() / width;
+    let size = tile_set.tile_size;
+    let mut output_image: RgbImage = RgbImage::new(width as u32 * size, depth as u32 * size);
+
+    graph
+        .vertices
+        .iter()
+        .enumerate()
+        .for_each(|(index, labels)| {
+            let tiles: Vec<&RgbImage> = labels
+                .into_iter()
+                .enumerate()
+                .filter(|(_, frequency)| frequency > &0)
+                .filter_map(|(label, _)| tile_set.bitmaps.get(label))
+                .collect();
+            let (x, y) = index_to_coords(index, width);
+            imageops::replace(&mut output_image, &blend(&tiles, size), x as u32 * size, y as u32 * size);
+        });
+
+    output_image
+}
+
+fn blend(tiles: &[&RgbImage], size: u32) -> RgbImage {
+    match tiles {
+        [] => RgbImage::from_pixel(size, size, CONTRADICT_COLOUR),
+        [tile] => (*tile).clone(),
+        _ => RgbImage::from_fn(size, size, |x, y| {
+            let sum = tiles
+                .iter()
+                .fold([0usize; 3], |mut acc, tile| {
+                    acc.iter_mut().zip(tile.get_pixel(x, y).0.iter()).for_each(|(a, c)| *a += *c as usize);
+                    acc
+                });
+            Rgb([
+                (sum[0] / tiles.len()) as u8,
+                (sum[1] / tiles.len()) as u8,
+                (sum[2] / tiles.len()) as u8,
+            ])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::tiled::tiled_parser::parse;
+    use crate::MSu16xNU;
+    use hashbrown::HashMap;
+
+    #[test]
+    fn test_render_tiles() {
+        let (_, all_labels, tile_set) = parse("resources/test/tiles").unwrap();
+        let single = |label: usize| {
+            let mut labels = MSu16xNU::empty();
+            labels.insert(label, 1);
+            labels
+        };
+        // corner 0, line 1 | contradiction, uncollapsed
+        let vertices = vec![single(3), single(2), MSu16xNU::empty(), all_labels];
+        let image = render_image(Graph::new(vertices, HashMap::new(), all_labels), &tile_set, 2);
+
+        assert_eq!(image.dimensions(), (6, 6));
+        assert_eq!(imageops::crop_imm(&image, 0, 0, 3, 3).to_image(), tile_set.bitmaps[3]);
+        assert_eq!(imageops::crop_imm(&image, 3, 0, 3, 3).to_image(), tile_set.bitmaps[2]);
+        assert_eq!(image.get_pixel(1, 4), &CONTRADICT_COLOUR);
+        // every tile variant is white in its corners
+        assert_eq!(image.get_pixel(3, 3), &Rgb([255, 255, 255]));
+    }
+}
@@ -1,6 +1,9 @@
-use crate::graph::graph::{Edges, VertexIndex};
+use crate::graph::graph::{EdgeDirection, Edges, VertexIndex};
+use crate::utils::{coords_to_index, is_inside};
 use hashbrown::HashMap;
+use itertools::Itertools;
 use nalgebra::DMatrix;
+use std::io::{Error, ErrorKind};
 
 //              +--- index of neighbor
 //              |    +-- direction of neighbor
@@ -8,6 +11,51 @@ use nalgebra::DMatrix;
 //              v    v
 type IdxDir = (u32, u16);
 
+//                 +--- x offset
+//                 |    +--- y offset
+//                 |    |    +-- direction
+//                 |    |    |
+//                 v    v    v
+const OFFSETS: [(i32, i32, u16); 8] = [
+    (-1, -1, 0), // NORTH WEST
+    (0, -1, 1),  // NORTH
+    (1, -1, 2),  // NORTH EAST
+    (-1, 0, 3),  // WEST
+    (1, 0, 4),   // EAST
+    (-1, 1, 5),  // SOUTH WEST
+    (0, 1, 6),   // SOUTH
+    (1, 1, 7),   // SOUTH EAST
+];
+
+/// Border behaviour of grid edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    None,       // clip at every border
+    Horizontal, // cylinder, west border joined to east border
+    Vertical,   // cylinder, north border joined to south border
+    Torus,      // both pairs of borders joined
+}
+
+impl Wrap {
+    pub(crate) fn axes(self) -> (bool, bool) {
+        match self {
+            Wrap::None => (false, false),
+            Wrap::Horizontal => (true, false),
+            Wrap::Vertical => (false, true),
+            Wrap::Torus => (true, true),
+        }
+    }
+}
+
+fn wrap_axis(position: i32, length: usize, wrap: bool) -> Option<u32> {
+    let length = length as i32;
+    if wrap {
+        Some(position.rem_euclid(length) as u32)
+    } else {
+        (0..length).contains(&position).then_some(position as u32)
+    }
+}
+
 pub struct Directions {
     code: u8,
 }
@@ -44,6 +92,125 @@ impl Directions {
         });
         edges
     }
+
+    /// Make grid edges where the borders selected by `wrap` are joined to their opposite border,
+    /// so that the output can be tiled seamlessly.
+    pub fn make_wrapped_edges(&self, width: usize, depth: usize, wrap: Wrap) -> Edges {
+        let mask = format!("{:08b}", self.code);
+        let offsets: Vec<(i32, i32, u16)> = OFFSETS
+            .iter()
+            .zip(mask.chars())
+            .filter(|(_, m)| *m == '1')
+            .map(|(offset, _)| *offset)
+            .collect();
+        let (wrap_x, wrap_y) = wrap.axes();
+        let mut edges = HashMap::new();
+        (0..depth).for_each(|depth_index| {
+            (0..width).for_each(|width_index| {
+                let direction_pairs = offsets
+                    .iter()
+                    .filter_map(|(x_offset, y_offset, direction)| {
+                        let x = wrap_axis(width_index as i32 + x_offset, width, wrap_x)?;
+                        let y = wrap_axis(depth_index as i32 + y_offset, depth, wrap_y)?;
+                        Some((y * width as u32 + x, *direction))
+                    })
+                    .collect();
+                let this_vertex_index = (depth_index * width + width_index) as VertexIndex;
+                edges.insert(this_vertex_index, direction_pairs);
+            });
+        });
+        edges
+    }
+}
+
+/// An arbitrary grid neighbourhood given as a list of `(dx, dy)` offsets.
+/// Each offset is assigned the direction id of its position in the list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stencil {
+    offsets: Vec<(i32, i32)>,
+}
+
+impl Stencil {
+    pub fn new(offsets: Vec<(i32, i32)>) -> Result<Stencil, Error> {
+        if offsets.contains(&(0, 0)) {
+            return Err(invalid_data("Stencil cannot contain the zero offset".to_string()))
+        }
+        if let Some((_, offset)) = offsets.iter().enumerate().find(|(i, offset)| offsets[..*i].contains(offset)) {
+            return Err(invalid_data(format!("Stencil offset {:?} is given more than once", offset)))
+        }
+        Ok(Stencil { offsets })
+    }
+
+    /// Every offset within `radius` steps of the centre, including diagonals.
+    /// `Stencil::square(1)` has the same direction ids as `make_edges_8_way_grid`.
+    pub fn square(radius: i32) -> Stencil {
+        Stencil {
+            offsets: (-radius..=radius)
+                .cartesian_product(-radius..=radius)
+                .map(|(y, x)| (x, y))
+                .filter(|offset| offset != &(0, 0))
+                .collect()
+        }
+    }
+
+    /// Only the offsets exactly `radius` steps from the centre.
+    pub fn ring(radius: i32) -> Stencil {
+        Stencil {
+            offsets: Stencil::square(radius)
+                .offsets
+                .into_iter()
+                .filter(|(x, y)| x.abs().max(y.abs()) == radius)
+                .collect()
+        }
+    }
+
+    pub fn knight() -> Stencil {
+        Stencil { offsets: vec![(-1, -2), (1, -2), (-2, -1), (2, -1), (-2, 1), (2, 1), (-1, 2), (1, 2)] }
+    }
+
+    pub fn offsets(&self) -> &[(i32, i32)] {
+        &self.offsets
+    }
+
+    pub fn direction(&self, offset: (i32, i32)) -> Option<EdgeDirection> {
+        self.offsets.iter().position(|o| o == &offset).map(|d| d as EdgeDirection)
+    }
+
+    /// The direction id pointing back along `direction`, if the stencil contains it.
+    pub fn opposite(&self, direction: EdgeDirection) -> Option<EdgeDirection> {
+        let (x, y) = *self.offsets.get(direction as usize)?;
+        self.direction((-x, -y))
+    }
+
+    pub fn make_edges(&self, width: usize, depth: usize) -> Edges {
+        self.make_wrapped_edges(width, depth, Wrap::None)
+    }
+
+    pub fn make_wrapped_edges(&self, width: usize, depth: usize, wrap: Wrap) -> Edges {
+        let (wrap_x, wrap_y) = wrap.axes();
+        let mut edges = HashMap::new();
+        (0..depth).for_each(|depth_index| {
+            (0..width).for_each(|width_index| {
+                let direction_pairs = self
+                    .offsets
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(direction, (x_offset, y_offset))| {
+                        let x = wrap_axis(width_index as i32 + x_offset, width, wrap_x)?;
+                        let y = wrap_axis(depth_index as i32 + y_offset, depth, wrap_y)?;
+                        Some((y * width as u32 + x, direction as EdgeDirection))
+                    })
+                    .collect();
+                let this_vertex_index = (depth_index * width + width_index) as VertexIndex;
+                edges.insert(this_vertex_index, direction_pairs);
+            });
+        });
+        edges
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
 }
 
 #[allow(unused_variables)]
@@ -99,6 +266,112 @@ pub fn make_edges_8_way_grid(width: usize, depth: usize) -> Edges {
     Directions::new(255).make_edges(width, depth)
 }
 
+pub fn make_edges_cardinal_grid_wrapped(width: usize, depth: usize, wrap: Wrap) -> Edges {
+    Directions::new(90).make_wrapped_edges(width, depth, wrap)
+}
+
+pub fn make_edges_8_way_grid_wrapped(width: usize, depth: usize, wrap: Wrap) -> Edges {
+    Directions::new(255).make_wrapped_edges(width, depth, wrap)
+}
+
+// Hex grids are stored in "odd-r" offset layout: each odd row is shifted half a cell east.
+// Neighbours are found in axial coordinates, where q runs east and r runs south east.
+//                    +--- q offset
+//                    |    +--- r offset
+//                    |    |    +-- direction
+//                    |    |    |
+//                    v    v    v
+const HEX_OFFSETS: [(i32, i32, u16); 6] = [
+    (0, -1, 0),  // NORTH WEST
+    (1, -1, 1),  // NORTH EAST
+    (-1, 0, 2),  // WEST
+    (1, 0, 3),   // EAST
+    (-1, 1, 4),  // SOUTH WEST
+    (0, 1, 5),   // SOUTH EAST
+];
+
+pub fn offset_to_axial((col, row): (i32, i32)) -> (i32, i32) {
+    (col - (row - (row & 1)) / 2, row)
+}
+
+pub fn axial_to_offset((q, r): (i32, i32)) -> (i32, i32) {
+    (q + (r - (r & 1)) / 2, r)
+}
+
+pub fn make_edges_hex_grid(width: usize, depth: usize) -> Edges {
+    let mut edges = HashMap::new();
+    (0..depth).for_each(|depth_index| {
+        (0..width).for_each(|width_index| {
+            let (q, r) = offset_to_axial((width_index as i32, depth_index as i32));
+            let direction_pairs = HEX_OFFSETS
+                .iter()
+                .filter_map(|(q_offset, r_offset, direction)| {
+                    let (x, y) = axial_to_offset((q + q_offset, r + r_offset));
+                    is_inside((x, y), (width, depth))
+                        .then(|| (coords_to_index(x as usize, y as usize, width) as VertexIndex, *direction))
+                })
+                .collect();
+            let this_vertex_index = (depth_index * width + width_index) as VertexIndex;
+            edges.insert(this_vertex_index, direction_pairs);
+        });
+    });
+    edges
+}
+
+// Voxel directions are numbered by iterating z, then y, then x offsets over -1..=1,
+// skipping the zero offset, so the opposite of direction d is always 25 - d.
+fn voxel_offsets() -> impl Iterator<Item=((i32, i32, i32), u16)> {
+    (-1..=1)
+        .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| (x, y, z))))
+        .filter(|offset| offset != &(0, 0, 0))
+        .zip(0..)
+}
+
+fn make_edges_voxel_grid(
+    width: usize,
+    depth: usize,
+    height: usize,
+    offsets: &[((i32, i32, i32), u16)],
+) -> Edges {
+    let layer = width * depth;
+    let mut edges = HashMap::new();
+    (0..height).for_each(|height_index| {
+        (0..depth).for_each(|depth_index| {
+            (0..width).for_each(|width_index| {
+                let direction_pairs = offsets
+                    .iter()
+                    .filter_map(|((x_offset, y_offset, z_offset), direction)| {
+                        let x = width_index as i32 + x_offset;
+                        let y = depth_index as i32 + y_offset;
+                        let z = height_index as i32 + z_offset;
+                        (is_inside((x, y), (width, depth)) && (0..height as i32).contains(&z))
+                            .then(|| ((z as usize * layer + y as usize * width + x as usize) as VertexIndex, *direction))
+                    })
+                    .collect();
+                let this_vertex_index = (height_index * layer + depth_index * width + width_index) as VertexIndex;
+                edges.insert(this_vertex_index, direction_pairs);
+            });
+        });
+    });
+    edges
+}
+
+/// Make edges for a voxel grid of `height` layers of `width` x `depth` vertices,
+/// connecting each voxel to the 6 voxels that share a face with it.
+pub fn make_edges_6_way_voxel_grid(width: usize, depth: usize, height: usize) -> Edges {
+    let offsets: Vec<_> = voxel_offsets()
+        .filter(|((x, y, z), _)| x.abs() + y.abs() + z.abs() == 1)
+        .collect();
+    make_edges_voxel_grid(width, depth, height, &offsets)
+}
+
+/// Make edges for a voxel grid of `height` layers of `width` x `depth` vertices,
+/// connecting each voxel to all 26 voxels surrounding it.
+pub fn make_edges_26_way_voxel_grid(width: usize, depth: usize, height: usize) -> Edges {
+    let offsets: Vec<_> = voxel_offsets().collect();
+    make_edges_voxel_grid(width, depth, height, &offsets)
+}
+
 pub trait Rotation {
     fn rotate_90(&self) -> Self;
 }
@@ -179,6 +452,173 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_make_edges_wrapped_none() {
+        assert_eq!(make_edges_cardinal_grid_wrapped(3, 2, Wrap::None), make_edges_cardinal_grid(3, 2));
+        assert_eq!(make_edges_8_way_grid_wrapped(3, 2, Wrap::None), make_edges_8_way_grid(3, 2));
+    }
+
+    #[test]
+    fn test_make_edges_cardinal_torus() {
+        /*
+        0 1 2
+        3 4 5
+        6 7 8
+         */
+        let result = make_edges_cardinal_grid_wrapped(3, 3, Wrap::Torus);
+        assert_eq!(result.get(&0).unwrap(), &vec![(6, 1), (2, 3), (1, 4), (3, 6)]);
+        assert_eq!(result.get(&4).unwrap(), &vec![(1, 1), (3, 3), (5, 4), (7, 6)]);
+        assert_eq!(result.get(&8).unwrap(), &vec![(5, 1), (7, 3), (6, 4), (2, 6)]);
+    }
+
+    #[test]
+    fn test_make_edges_cardinal_cylinder() {
+        /*
+        0 1 2
+        3 4 5
+         */
+        let horizontal = make_edges_cardinal_grid_wrapped(3, 2, Wrap::Horizontal);
+        assert_eq!(horizontal.get(&0).unwrap(), &vec![(2, 3), (1, 4), (3, 6)]);
+        assert_eq!(horizontal.get(&5).unwrap(), &vec![(2, 1), (4, 3), (3, 4)]);
+
+        let vertical = make_edges_cardinal_grid_wrapped(3, 2, Wrap::Vertical);
+        assert_eq!(vertical.get(&0).unwrap(), &vec![(3, 1), (1, 4), (3, 6)]);
+        assert_eq!(vertical.get(&5).unwrap(), &vec![(2, 1), (4, 3), (2, 6)]);
+    }
+
+    #[test]
+    fn test_make_edges_8_way_torus() {
+        let result = make_edges_8_way_grid_wrapped(3, 3, Wrap::Torus);
+        assert!(result.values().all(|neighbours| neighbours.len() == 8));
+        assert_eq!(
+            result.get(&0).unwrap(),
+            &vec![(8, 0), (6, 1), (7, 2), (2, 3), (1, 4), (5, 5), (3, 6), (4, 7)]
+        );
+    }
+
+    #[test]
+    fn test_stencil_square_matches_8_way() {
+        let stencil = Stencil::square(1);
+        assert_eq!(stencil.make_edges(3, 3), make_edges_8_way_grid(3, 3));
+        assert_eq!(Stencil::ring(1), stencil);
+    }
+
+    #[test]
+    fn test_stencil_ring() {
+        let stencil = Stencil::ring(2);
+        assert_eq!(stencil.offsets().len(), 16);
+        assert_eq!(Stencil::square(2).offsets().len(), 24);
+        assert_eq!(stencil.direction((-2, -2)), Some(0));
+        assert_eq!(stencil.direction((0, 0)), None);
+        assert_eq!(stencil.direction((1, 1)), None);
+    }
+
+    #[test]
+    fn test_stencil_opposite() {
+        let stencil = Stencil::knight();
+        (0..8).for_each(|direction| {
+            assert_eq!(stencil.opposite(direction), Some(7 - direction));
+        });
+        assert_eq!(Stencil::new(vec![(1, 0), (0, 1)]).unwrap().opposite(0), None);
+        assert_eq!(stencil.opposite(8), None);
+    }
+
+    #[test]
+    fn test_stencil_knight_edges() {
+        /*
+        0  1  2
+        3  4  5
+        6  7  8
+         */
+        let result = Stencil::knight().make_edges(3, 3);
+        assert_eq!(result.get(&0).unwrap(), &vec![(5, 5), (7, 7)]);
+        assert_eq!(result.get(&8).unwrap(), &vec![(1, 0), (3, 2)]);
+        // the centre cannot be reached by knight moves
+        assert!(result.get(&4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stencil_wrapped_edges() {
+        let result = Stencil::new(vec![(2, 0)]).unwrap().make_wrapped_edges(3, 1, Wrap::Horizontal);
+        assert_eq!(result.get(&0).unwrap(), &vec![(2, 0)]);
+        assert_eq!(result.get(&2).unwrap(), &vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_stencil_errors() {
+        let zero = Stencil::new(vec![(0, 0), (1, 0)]).unwrap_err();
+        assert_eq!(zero.kind(), ErrorKind::InvalidData);
+        let repeated = Stencil::new(vec![(1, 0), (0, 1), (1, 0)]).unwrap_err();
+        assert_eq!(repeated.kind(), ErrorKind::InvalidData);
+        assert!(repeated.to_string().contains("(1, 0)"));
+    }
+
+    #[test]
+    fn test_axial_round_trip() {
+        (-3..4).for_each(|row| {
+            (-3..4).for_each(|col| {
+                assert_eq!(axial_to_offset(offset_to_axial((col, row))), (col, row));
+            })
+        });
+    }
+
+    #[test]
+    fn test_make_edges_hex_grid() {
+        /*
+        0 1 2
+         3 4 5
+        6 7 8
+         */
+        let result = make_edges_hex_grid(3, 3);
+        let expected = hash_map(&[
+            (0, vec![(1, 3), (3, 5)]),
+            (1, vec![(0, 2), (2, 3), (3, 4), (4, 5)]),
+            (2, vec![(1, 2), (4, 4), (5, 5)]),
+            (3, vec![(0, 0), (1, 1), (4, 3), (6, 4), (7, 5)]),
+            (4, vec![(1, 0), (2, 1), (3, 2), (5, 3), (7, 4), (8, 5)]),
+            (5, vec![(2, 0), (4, 2), (8, 4)]),
+            (6, vec![(3, 1), (7, 3)]),
+            (7, vec![(3, 0), (4, 1), (6, 2), (8, 3)]),
+            (8, vec![(4, 0), (5, 1), (7, 2)]),
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_voxel_offsets_opposites() {
+        let offsets: Vec<_> = voxel_offsets().collect();
+        assert_eq!(offsets.len(), 26);
+        offsets.iter().for_each(|((x, y, z), direction)| {
+            assert_eq!(offsets[25 - *direction as usize].0, (-x, -y, -z));
+        });
+    }
+
+    #[test]
+    fn test_make_edges_6_way_voxel_grid() {
+        /*
+        layer 0:  layer 1:
+        0 1       4 5
+        2 3       6 7
+
+        BELOW = 4, NORTH = 10, WEST = 12, EAST = 13, SOUTH = 15, ABOVE = 21
+         */
+        let result = make_edges_6_way_voxel_grid(2, 2, 2);
+        assert_eq!(result.get(&0).unwrap(), &vec![(1, 13), (2, 15), (4, 21)]);
+        assert_eq!(result.get(&7).unwrap(), &vec![(3, 4), (5, 10), (6, 12)]);
+        assert!(result.values().all(|neighbours| neighbours.len() == 3));
+    }
+
+    #[test]
+    fn test_make_edges_26_way_voxel_grid() {
+        let result = make_edges_26_way_voxel_grid(3, 3, 3);
+        assert_eq!(result.len(), 27);
+        // the centre voxel touches every other voxel
+        assert_eq!(result.get(&13).unwrap().len(), 26);
+        // corner voxels touch the 7 other voxels in their 2x2x2 corner
+        assert_eq!(result.get(&0).unwrap().len(), 7);
+        assert_eq!(result.get(&26).unwrap().len(), 7);
+    }
+
     #[test]
     fn test_rotation_2x2() {
         /*
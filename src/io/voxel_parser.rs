@@ -0,0 +1,149 @@
+use crate::graph::graph::{Edges, Graph};
+use crate::io::text_parser::{rows, token_maps, vertex_token, Tokens, Undecided};
+use crate::io::utils::{invalid_data, make_edges_6_way_voxel_grid, make_edges_26_way_voxel_grid};
+use indexmap::IndexMap;
+use std::fs::{read_to_string, write};
+use std::io::Error;
+
+// Voxel samples are a stack of text layers, from the bottom layer up, separated by an empty line.
+// Every layer must have the same number of lines, all the same width.
+pub fn parse(filename: &str, full_neighbourhood: bool) -> Result<(Graph, IndexMap<String, u16>), Error> {
+    let string = read_to_string(filename)?.replace("\r\n", "\n");
+    let layer_strings = layer_strings(&string);
+    let layers = layer_strings
+        .iter()
+        .map(|layer| rows(layer, Tokens::Graphemes))
+        .collect::<Result<Vec<_>, Error>>()?;
+    check_layers(&layers)?;
+    let edges = make_edges(&layers, full_neighbourhood);
+    let tokens = layers.concat().concat().into_iter();
+    let (token_frequency, vertices) = token_maps(tokens)?;
+    let all_labels = token_frequency.values().collect();
+    Ok((Graph::new(vertices, edges, all_labels), token_frequency))
+}
+
+// each layer with its own trailing newline, so it can be split into rows
+fn layer_strings(string: &str) -> Vec<String> {
+    string
+        .split("\n\n")
+        .map(|layer| layer.trim_matches('\n'))
+        .filter(|layer| !layer.is_empty())
+        .map(|layer| format!("{}\n", layer))
+        .collect()
+}
+
+fn check_layers(layers: &[Vec<Vec<&str>>]) -> Result<(), Error> {
+    let first = layers.first().ok_or_else(|| invalid_data("Voxel sample is empty".to_string()))?;
+    let (depth, width) = (first.len(), first[0].len());
+    layers
+        .iter()
+        .enumerate()
+        .try_for_each(|(index, layer)| {
+            if layer.len() != depth {
+                return Err(invalid_data(format!(
+                    "Layer {} is {} lines deep but the first layer is {} lines deep", index + 1, layer.len(), depth
+                )))
+            }
+            if layer[0].len() != width {
+                return Err(invalid_data(format!(
+                    "Layer {} is {} tokens wide but the first layer is {} tokens wide", index + 1, layer[0].len(), width
+                )))
+            }
+            Ok(())
+        })
+}
+
+fn make_edges(layers: &[Vec<Vec<&str>>], full_neighbourhood: bool) -> Edges {
+    let depth = layers[0].len();
+    let width = layers[0][0].len();
+    if full_neighbourhood {
+        make_edges_26_way_voxel_grid(width, depth, layers.len())
+    } else {
+        make_edges_6_way_voxel_grid(width, depth, layers.len())
+    }
+}
+
+pub fn render(
+    filename: &str,
+    graph: Graph,
+    key: &IndexMap<String, u16>,
+    width: usize,
+    depth: usize,
+) {
+    let undecided = Undecided::default();
+    let layers: Vec<String> = graph
+        .vertices
+        .chunks_exact(width * depth)
+        .map(|layer| {
+            layer
+                .chunks_exact(width)
+                .map(|line| {
+                    line
+                        .iter()
+                        .map(|labels| vertex_token(labels, key, &undecided))
+                        .chain(std::iter::once("\n".into()))
+                        .collect::<String>()
+                })
+                .collect()
+        })
+        .collect();
+    if write(filename, layers.join("\n")).is_ok() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_parse_voxels() {
+        let (graph, keys) = parse("resources/test/voxel_sample.txt", false).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(graph.vertices.len(), 48);
+        assert_eq!(graph.edges.len(), 48);
+
+        let rules = graph.rules();
+        let floor = keys.get_index_of("#").unwrap();
+        let roof = keys.get_index_of("^").unwrap();
+        // walls stand on the floor and nothing is ever above the roof
+        assert!(rules.get(&(4, floor)).unwrap().into_iter().nth(floor).unwrap() > 0);
+        assert!(rules.get(&(21, roof)).is_none());
+    }
+
+    #[test]
+    fn test_parse_voxels_26_way() {
+        let (graph, _) = parse("resources/test/voxel_sample.txt", true).unwrap();
+        // a voxel in the middle of the second layer touches every voxel around it
+        assert_eq!(graph.edges.get(&17).unwrap().len(), 26);
+    }
+
+    #[test]
+    fn test_parse_voxels_crlf() {
+        let filename = "resources/test/voxel_crlf_test.txt";
+        write(filename, read_to_string("resources/test/voxel_sample.txt").unwrap().replace('\n', "\r\n")).unwrap();
+        let parsed = parse(filename, false);
+        std::fs::remove_file(filename).unwrap();
+
+        let (graph, keys) = parsed.unwrap();
+        let (sample_graph, sample_keys) = parse("resources/test/voxel_sample.txt", false).unwrap();
+        assert_eq!(keys, sample_keys);
+        assert_eq!(graph.vertices, sample_graph.vertices);
+        assert_eq!(graph.edges, sample_graph.edges);
+    }
+
+    #[test]
+    fn test_parse_voxels_errors() {
+        let filename = "resources/test/voxel_error_test.txt";
+        let samples = [
+            "##\n##\n\n##\n",     // layers of different depths
+            "##\n##\n\n###\n###\n", // layers of different widths
+            "##\n#\n\n##\n##\n",    // ragged line
+        ];
+        samples.iter().for_each(|sample| {
+            write(filename, sample).unwrap();
+            let parsed = parse(filename, false);
+            std::fs::remove_file(filename).unwrap();
+            assert_eq!(parsed.unwrap_err().kind(), ErrorKind::InvalidData);
+        });
+    }
+}
@@ -1,67 +1,151 @@
 use wfc_rust::graph::graph::Graph;
 use wfc_rust::io::text_parser;
-use wfc_rust::io::utils::{make_edges_cardinal_grid, make_edges_8_way_grid};
-use wfc_rust::wfc::collapse::{collapse, collapse_progress};
+use wfc_rust::io::hex_parser;
+use wfc_rust::io::utils::{make_edges_cardinal_grid_wrapped, make_edges_8_way_grid_wrapped, make_edges_hex_grid, Wrap};
+use wfc_rust::wfc::collapse::{collapse_owned, collapse_progress};
 use wfc_rust::io::olm::olm_renderer;
-use wfc_rust::io::olm::olm_parser;
-use wfc_rust::io::post_processors::unit_image::UnitImage;
-use image::Rgb;
+use wfc_rust::io::olm::olm_parser::{self, ChunkSize, ChunkSymmetry, Pruning};
+use wfc_rust::io::olm::olm_text_parser;
+use wfc_rust::io::persist;
+use wfc_rust::io::tiled::{tiled_parser, tiled_renderer};
 use wfc_rust::io::post_processors::rescale_image::RescaleImage;
 
-// TODO: Save the results of parsing
-
-fn run_tile(input: &str, output: &str, width: usize, depth: usize, intercardinals: bool) {
+fn run_tile(input: &str, output: &str, width: usize, depth: usize, intercardinals: bool, wrap: Wrap) {
     if let Ok((input_graph, keys)) = text_parser::parse(input, intercardinals) {
         let all_labels = input_graph.all_labels;
         let output_vertices = vec![all_labels; width * depth];
         let output_edges = if intercardinals {
-            make_edges_8_way_grid(width, depth)
+            make_edges_8_way_grid_wrapped(width, depth, wrap)
         } else {
-            make_edges_cardinal_grid(width, depth)
+            make_edges_cardinal_grid_wrapped(width, depth, wrap)
         };
         let output_graph = Graph::new(output_vertices, output_edges, all_labels);
-        let collapsed_graph = collapse(&input_graph.rules(), &output_graph, None, None);
+        let collapsed_graph = collapse_owned(&input_graph.rules(), output_graph, None, None);
         text_parser::render(output, collapsed_graph, &keys, width);
     }
 }
 
-fn run_olm(input: &str, chunk_size: usize, output: &str, width: usize, depth: usize) {
-    if width % chunk_size != 0 || depth % chunk_size != 0 {
-        panic!("Output dimensions and N size NOT divisible.");
+fn run_hex(input: &str, output: &str, width: usize, depth: usize) {
+    if let Ok((input_graph, keys)) = hex_parser::parse(input) {
+        let all_labels = input_graph.all_labels;
+        let output_vertices = vec![all_labels; width * depth];
+        let output_graph = Graph::new(output_vertices, make_edges_hex_grid(width, depth), all_labels);
+        let collapsed_graph = collapse_owned(&input_graph.rules(), output_graph, None, None);
+        hex_parser::render(output, collapsed_graph, &keys, width);
+    }
+}
+
+fn run_tiled(input: &str, output: &str, width: usize, depth: usize, wrap: Wrap) {
+    if let Ok((rules, all_labels, tile_set)) = tiled_parser::parse(input) {
+        let output_vertices = vec![all_labels; width * depth];
+        let output_edges = make_edges_cardinal_grid_wrapped(width, depth, wrap);
+        let output_graph = Graph::new(output_vertices, output_edges, all_labels);
+        let collapsed_graph = collapse_owned(&rules, output_graph, None, None);
+        tiled_renderer::render(output, collapsed_graph, &tile_set, width);
     }
-    let (rules, keys, all_labels, chunks) = olm_parser::parse(input, chunk_size);
-    let graph_width = width / chunk_size; // in chunks
-    let graph_depth = depth / chunk_size; // in chunks
-    let output_edges = make_edges_8_way_grid(graph_width, graph_depth);
+}
+
+fn run_text_olm(input: &str, chunk_size: ChunkSize, output: &str, width: usize, depth: usize, wrap: Wrap) {
+    match olm_text_parser::parse(input, chunk_size, &ChunkSymmetry::default(), false, PRUNING) {
+        Ok((rules, keys, all_labels, chunks)) => {
+            // chunks overhanging the output are cropped when rendering, wrapped axes are kept whole
+            let graph_width = olm_renderer::chunks_to_cover(width, chunk_size.0); // in chunks
+            let graph_depth = olm_renderer::chunks_to_cover(depth, chunk_size.1); // in chunks
+            let output_edges = make_edges_8_way_grid_wrapped(graph_width, graph_depth, wrap);
+            let output_vertices = vec![all_labels; graph_width * graph_depth];
+            let output_graph = Graph::new(output_vertices, output_edges, all_labels);
+            let collapsed_graph = collapse_owned(&rules, output_graph, None, None);
+            olm_text_parser::render(output, collapsed_graph, &keys, &chunks, (width, depth), chunk_size, wrap);
+        },
+        Err(e) => println!("Could not parse {}: {}", input, e)
+    }
+}
+
+fn run_olm(input: &str, chunk_size: ChunkSize, output: &str, width: usize, depth: usize, wrap: Wrap) {
+    let (chunk_width, chunk_height) = chunk_size;
+    let symmetry = ChunkSymmetry::default();
+    let parse = || olm_parser::parse(input, chunk_size, &symmetry, PERIODIC, PRUNING);
+    // reuse the results of a previous parse of the same sample and options, parsing is the slowest step
+    let fingerprint = persist::olm_fingerprint(&[input], chunk_size, &symmetry, PERIODIC, PRUNING);
+    let (rules, keys, all_labels, chunks) = match (OLM_CACHE, fingerprint) {
+        (Some(cache), Ok(fingerprint)) => persist::load_olm(cache, fingerprint).unwrap_or_else(|_| {
+            let parsed = parse();
+            let (rules, keys, all_labels, chunks) = &parsed;
+            if let Err(e) = persist::save_olm(cache, fingerprint, rules, keys, all_labels, chunks) {
+                println!("Could not save parsed rules to {}: {}", cache, e)
+            }
+            parsed
+        }),
+        _ => parse()
+    };
+    // chunks overhanging the output are cropped when rendering, wrapped axes are kept whole
+    let graph_width = olm_renderer::chunks_to_cover(width, chunk_width); // in chunks
+    let graph_depth = olm_renderer::chunks_to_cover(depth, chunk_height); // in chunks
+    let output_edges = make_edges_8_way_grid_wrapped(graph_width, graph_depth, wrap);
     let output_vertices = vec![all_labels; graph_width * graph_depth];
     let output_graph = Graph::new(output_vertices, output_edges, all_labels);
-    // let collapsed_graph = collapse(&rules, &output_graph, None, None);
-    let collapsed_vertices = collapse_progress(&rules, &output_graph, None);
+    // let collapsed_graph = collapse_owned(&rules, output_graph, None, None);
+    let collapsed_vertices = collapse_progress(&rules, output_graph, None);
     // image_olm_parser::render(output, collapsed_graph, &keys, &chunks, (width, depth), chunk_size as usize);
     // image_olm_parser::progress_render(output, collapsed_vertices, &keys, &chunks, (width, depth), chunk_size as usize);
     let post_processors = Some(vec![RescaleImage::new(10)]);
-    // olm_renderer::render(output, collapsed_graph, &keys, &chunks, (width, depth), chunk_size as usize, &post_processors);
-    olm_renderer::progress(output, collapsed_vertices, &keys, &chunks, (width, depth), chunk_size as usize, &post_processors);
+    // olm_renderer::render(output, collapsed_graph, &keys, &chunks, (width, depth), chunk_size, wrap, &post_processors);
+    olm_renderer::progress(output, collapsed_vertices, &keys, &chunks, (width, depth), chunk_size, wrap, &post_processors);
 
 }
 
 enum RunMode {
     OLM,
-    Tile
+    Tile,
+    Hex,
+    Tiled,
+    TextOLM
+}
+
+impl RunMode {
+    fn from_arg(arg: &str) -> Option<RunMode> {
+        match arg {
+            "olm" => Some(RunMode::OLM),
+            "tile" => Some(RunMode::Tile),
+            "hex" => Some(RunMode::Hex),
+            "tiled" => Some(RunMode::Tiled),
+            "text-olm" => Some(RunMode::TextOLM),
+            _ => None
+        }
+    }
 }
 
-const CHUNK_SIZE: usize = 2;
+// (width, height) of OLM chunks
+const CHUNK_SIZE: ChunkSize = (2, 2);
+// used when no mode is given as the first argument
 const MODE: RunMode = RunMode::OLM;
+const WRAP: Wrap = Wrap::None;
+// tileable OLM samples
+const PERIODIC: bool = false;
+// Pruning::Direct parses samples with many chunks much faster, keeping a few more rules
+const PRUNING: Pruning = Pruning::Graph;
+// file to save parsed OLM rules to and reuse while the sample and options are unchanged, e.g. Some("target/olm_cache.json")
+const OLM_CACHE: Option<&str> = None;
 
 fn main() {
-    match MODE {
+    let mode = match std::env::args().nth(1) {
+        Some(arg) => match RunMode::from_arg(&arg) {
+            Some(mode) => mode,
+            None => {
+                println!("Unknown mode {}, expected one of olm, tile, hex, tiled, text-olm", arg);
+                return
+            }
+        },
+        None => MODE
+    };
+    match mode {
         RunMode::OLM => {
             let input = "resources/test/City.png";
             let output = "resources/test/test_result_9.png";
             let out_width = 20;
             let out_depth = 20;
 
-            run_olm(input, CHUNK_SIZE, output, out_width, out_depth);
+            run_olm(input, CHUNK_SIZE, output, out_width, out_depth, WRAP);
         },
         RunMode::Tile => {
             let input = "resources/test/tosashimizu_model.txt";
@@ -69,7 +153,31 @@ fn main() {
             let out_width = 20;
             let out_depth = 20;
 
-            run_tile(input, output, out_width, out_depth, true);
+            run_tile(input, output, out_width, out_depth, true, WRAP);
+        },
+        RunMode::Hex => {
+            let input = "resources/test/hex_sample.txt";
+            let output = "resources/test/hex_output.txt";
+            let out_width = 20;
+            let out_depth = 20;
+
+            run_hex(input, output, out_width, out_depth);
+        },
+        RunMode::Tiled => {
+            let input = "resources/test/tiles";
+            let output = "resources/test/tiled_output.png";
+            let out_width = 20;
+            let out_depth = 20;
+
+            run_tiled(input, output, out_width, out_depth, WRAP);
+        },
+        RunMode::TextOLM => {
+            let input = "resources/test/ascii_rooms.txt";
+            let output = "resources/test/ascii_rooms_output.txt";
+            let out_width = 40;
+            let out_depth = 20;
+
+            run_text_olm(input, CHUNK_SIZE, output, out_width, out_depth, WRAP);
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::graph::graph::{EdgeDirection, Edges, Graph, Rules, VertexIndex, Vertices};
+use crate::graph::graph::{CompactEdges, EdgeDirection, Edges, Graph, Rules, VertexIndex, Vertices};
 use crate::wfc::observe::Observe;
 use crate::wfc::propagate::Propagate;
 use rand::prelude::*;
@@ -19,7 +19,7 @@ type InitCollapse = (
     BinaryHeap<Observe>,  // heap
 );
 
-fn init_collapse(rng: &mut SmallRng, out_graph: &Graph) -> InitCollapse {
+fn init_collapse(rng: &mut SmallRng, out_graph: &Graph, edges: &CompactEdges) -> InitCollapse {
     let mut observed: BitSet = BitSet::new();
     let mut propagations: Vec<Propagate> = Vec::new();
     let mut init_propagations: Vec<VertexIndex> = Vec::new();
@@ -44,7 +44,7 @@ fn init_collapse(rng: &mut SmallRng, out_graph: &Graph) -> InitCollapse {
 
     // Ensure that output graph will be fully propagated before further collapse.
     init_propagations.drain(..).for_each(|index| {
-        generate_propagations(&mut propagations, &observed, &out_graph.edges, index);
+        generate_propagations(&mut propagations, &observed, edges, index);
     });
 
     let to_observe_len = out_graph.vertices.len() as VertexIndex;
@@ -62,7 +62,7 @@ const OBSERVE_CHANCE: usize = 75;
 fn exec_collapse(
     rng: &mut SmallRng,
     rules: &Rules,
-    edges: &Edges,
+    edges: &CompactEdges,
     init: InitCollapse,
     mut vertices: Vec<MSu16xNU>,
     iterations: Option<usize>,
@@ -182,11 +182,11 @@ pub fn build_constraint(labels: &MSu16xNU, direction: EdgeDirection, rules: &Rul
 fn generate_propagations(
     propagations: &mut Vec<Propagate>,
     observed: &BitSet,
-    edges: &Edges,
+    edges: &CompactEdges,
     from_index: VertexIndex,
 ) {
-    assert!(edges.contains_key(&from_index));
-    for (to_index, direction) in edges.index(&from_index) {
+    assert!(edges.len() > from_index as usize);
+    for (to_index, direction) in edges.neighbours(from_index) {
         if !observed.contains(*to_index as usize) {
             propagations.push(Propagate::new(from_index, *to_index, *direction))
         }
@@ -196,17 +196,18 @@ fn generate_propagations(
 fn _collapse(
     rules: &Rules,
     output_graph: &Graph,
+    edges: &CompactEdges,
     seed: Option<u64>,
     iterations: Option<usize>,
     progress: bool
 ) -> Vec<Vertices> {
     let rng = &mut SmallRng::seed_from_u64(seed.unwrap_or_else(|| thread_rng().next_u64()));
-    let init = init_collapse(rng, &output_graph);
+    let init = init_collapse(rng, output_graph, edges);
 
     exec_collapse(
         rng,
         rules,
-        &output_graph.edges,
+        edges,
         init,
         output_graph.vertices.clone(),
         iterations,
@@ -214,15 +215,17 @@ fn _collapse(
     )
 }
 
-// Public interface for single graph collapses
+// Public interface for single graph collapses.
+// Edges are compiled once into a compact adjacency for propagation, the borrowed edge map is kept alongside it.
 pub fn collapse(
     rules: &Rules,
     output_graph: &Graph,
     seed: Option<u64>,
     iterations: Option<usize>
 ) -> Graph {
-
-    let collapsed_vertices = _collapse(rules, output_graph, seed, iterations, false);
+    let edges = output_graph.compact_edges();
+    let collapsed_vertices = _collapse(rules, output_graph, &edges, seed, iterations, false);
+    drop(edges);
 
     Graph::new(
         collapsed_vertices.last().unwrap().clone(),
@@ -231,19 +234,41 @@ pub fn collapse(
     )
 }
 
-// Public interface for progress collapses
+// Single graph collapse for large outputs: the edge map is dropped before propagation, so only the
+// compact adjacency is held while collapsing, and the map is rebuilt from it for the collapsed graph.
+pub fn collapse_owned(
+    rules: &Rules,
+    mut output_graph: Graph,
+    seed: Option<u64>,
+    iterations: Option<usize>
+) -> Graph {
+    let edges = output_graph.compact_edges();
+    output_graph.edges = Edges::new();
+    let collapsed_vertices = _collapse(rules, &output_graph, &edges, seed, iterations, false);
+
+    Graph::new(
+        collapsed_vertices.into_iter().last().unwrap(),
+        edges.to_edges(),
+        output_graph.all_labels
+    )
+}
+
+// Public interface for progress collapses, the edge map is dropped before propagation as in `collapse_owned`.
 pub fn collapse_progress(
     rules: &Rules,
-    output_graph: &Graph,
+    mut output_graph: Graph,
     seed: Option<u64>,
 ) -> Vec<Vertices> {
-    _collapse(rules, output_graph, seed, None, true)
+    let edges = output_graph.compact_edges();
+    output_graph.edges = Edges::new();
+    _collapse(rules, &output_graph, &edges, seed, None, true)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::graph::graph::Edges;
+    use crate::io::utils::{make_edges_cardinal_grid, make_edges_cardinal_grid_wrapped, Wrap};
     use crate::utils::hash_map;
     use std::iter::FromIterator;
 
@@ -313,9 +338,10 @@ mod tests {
             ((3, 2), [0, 2, 0].iter().collect()),
         ]);
 
-        let init = init_collapse(&mut rng, &out_graph);
+        let edges = out_graph.compact_edges();
+        let init = init_collapse(&mut rng, &out_graph, &edges);
 
-        let result = exec_collapse(&mut rng, &rules, &out_graph.edges, init, simple_vertices(), None, false).into_iter().nth(0).unwrap();
+        let result = exec_collapse(&mut rng, &rules, &edges, init, simple_vertices(), None, false).into_iter().nth(0).unwrap();
         let expected: Vec<MSu16xNU> = vec![
             [1, 0, 0].iter().collect(),
             [0, 2, 0].iter().collect(),
@@ -364,12 +390,13 @@ mod tests {
         ]);
 
         let out_graph = Graph::new(vertices, edges, all_labels);
-        let init = init_collapse(&mut rng, &out_graph);
+        let edges = out_graph.compact_edges();
+        let init = init_collapse(&mut rng, &out_graph, &edges);
 
         let result = exec_collapse(
             &mut rng,
             &rules,
-            &out_graph.edges,
+            &edges,
             init,
             out_graph.vertices.clone(),
             None,
@@ -454,12 +481,13 @@ mod tests {
         let output_vertices: Vec<MSu16xNU> = vec![all_labels; 12];
 
         let output_graph = Graph::new(output_vertices, output_edges, all_labels);
-        let init = init_collapse(&mut rng, &output_graph);
+        let edges = output_graph.compact_edges();
+        let init = init_collapse(&mut rng, &output_graph, &edges);
 
         let result = exec_collapse(
             &mut rng,
             &rules,
-            &output_graph.edges,
+            &edges,
             init,
             output_graph.vertices.clone(),
             None,
@@ -486,4 +514,50 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_collapse_torus_seams() {
+        /*
+            INPUT graph:
+            0a --- 1b --- 2a --- 3b
+
+            Directions: North = 1, West = 3, East = 4, South = 6
+        */
+        let input_edges = make_edges_cardinal_grid(4, 1);
+        let input_vertices: Vec<MSu16xNU> = vec![
+            [1, 0].iter().collect(),
+            [0, 1].iter().collect(),
+            [1, 0].iter().collect(),
+            [0, 1].iter().collect(),
+        ];
+        let all_labels = MSu16xNU::from_iter([2, 2].iter().cloned());
+        let mut rules = Graph::new(input_vertices, input_edges, all_labels).rules();
+        // stripes run north to south
+        rules.insert((1, 0), [1, 0].iter().collect());
+        rules.insert((1, 1), [0, 1].iter().collect());
+        rules.insert((6, 0), [1, 0].iter().collect());
+        rules.insert((6, 1), [0, 1].iter().collect());
+
+        let output_edges = make_edges_cardinal_grid_wrapped(6, 4, Wrap::Torus);
+        let output_graph = Graph::new(vec![all_labels; 24], output_edges, all_labels);
+        let result = collapse(&rules, &output_graph, Some(7), None);
+
+        // every edge, including those crossing the seams, must satisfy the rules
+        result.edges.iter().for_each(|(from, neighbours)| {
+            let from_labels = result.vertices.index(*from as usize);
+            assert!(from_labels.is_singleton());
+            neighbours.iter().for_each(|(to, direction)| {
+                let to_labels = result.vertices.index(*to as usize);
+                let allowed = build_constraint(from_labels, *direction, &rules);
+                assert!(!to_labels.intersection(&allowed).is_empty());
+            })
+        });
+
+        // the owned collapses free the edge map while collapsing, and reach the same result
+        let owned = collapse_owned(&rules, output_graph.clone(), Some(7), None);
+        assert_eq!(owned.vertices, result.vertices);
+        assert_eq!(owned.edges, result.edges);
+        let frames = collapse_progress(&rules, output_graph, Some(7));
+        assert_eq!(frames.last(), Some(&result.vertices));
+    }
 }